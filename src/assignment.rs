@@ -1,28 +1,30 @@
+use crate::data_structure::database::Database;
 use crate::data_structure::query::Query;
-use crate::data_structure::reader::get_database_with_query;
 
 pub fn is_acyclic(query: &Query) -> bool {
     query.is_acyclic()
 }
 
-pub fn bool_answer(query: &Query) -> Option<bool> {
-    let database = get_database_with_query(query);
+pub fn bool_answer(query: &Query, catalog: &Database) -> Option<bool> {
     if query.is_boolean() {
+        let database = catalog.view(query);
         Some(query.yannakakis_boolean(&database))
     } else {
         None
     }
 }
 
-pub fn answers_query(query: &Query) -> [Vec<String>; 4] {
+pub fn answers_query(query: &Query, catalog: &Database) -> [Vec<String>; 4] {
     if query.is_boolean() {
         return [vec![], vec![], vec![], vec![]];
     }
-    let database = get_database_with_query(query);
-    let query = query.yannakakis(database);
+    // the rows are joined as the stream is pulled, under the deadline of the caller
+    let table = query
+        .stream(catalog)
+        .into_table(query.head.relation_name.clone());
     let mut answer: [Vec<String>; 4] = [vec![], vec![], vec![], vec![]];
-    for i in 0..query.data.num_columns() {
-        answer[i] = query.get_column_as_vec(i).unwrap();
+    for (i, column) in answer.iter_mut().enumerate().take(table.data.num_columns()) {
+        *column = table.get_column_as_vec(i).unwrap();
     }
     answer
 }
@@ -0,0 +1,148 @@
+use std::any::Any;
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::data_structure::database::Database;
+use crate::data_structure::interrupt::{Cancellation, CancellationToken, Interrupted};
+use crate::data_structure::query::Query;
+use crate::data_structure::table::Table;
+
+/// Why a job of a batch has no result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Failure {
+    /// The job panicked, with this message.
+    Error(String),
+    /// The token of the batch was cancelled before the job was done.
+    Cancelled,
+    /// The job ran past the timeout of the batch.
+    Timeout,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Error(message) => write!(f, "{}", message),
+            Failure::Cancelled => write!(f, "{}", Interrupted::Cancelled),
+            Failure::Timeout => write!(f, "{}", Interrupted::Timeout),
+        }
+    }
+}
+
+impl From<Interrupted> for Failure {
+    fn from(interrupted: Interrupted) -> Self {
+        match interrupted {
+            Interrupted::Cancelled => Failure::Cancelled,
+            Interrupted::Timeout => Failure::Timeout,
+        }
+    }
+}
+
+/// Runs independent jobs, typically queries, on a pool of threads that all read the same
+/// database. Results come back in the order of the jobs, and a job that fails (panics) only fails
+/// its own result. Jobs can be given a timeout each, and cancelled all at once through the token
+/// of the batch.
+pub struct BatchExecutor {
+    database: Arc<Database>,
+    threads: usize,
+    token: CancellationToken,
+    timeout: Option<Duration>,
+}
+
+impl BatchExecutor {
+    /// An executor using one thread per available core.
+    pub fn new(database: Arc<Database>) -> Self {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        BatchExecutor::with_threads(database, threads)
+    }
+
+    pub fn with_threads(database: Arc<Database>, threads: usize) -> Self {
+        BatchExecutor {
+            database,
+            threads: threads.max(1),
+            token: CancellationToken::new(),
+            timeout: None,
+        }
+    }
+
+    /// Stops every job that runs longer than `timeout`, counted from its own start.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Stops the jobs not done yet when `token` is cancelled.
+    pub fn with_token(mut self, token: CancellationToken) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// The token cancelling the jobs of this executor.
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.database
+    }
+
+    /// Applies `job` to every item against the shared database. Threads take the next item as
+    /// soon as they are done with one, so long jobs do not hold back the rest of the batch. The
+    /// result of an item is its panic message if `job` panicked on it, or the reason it was
+    /// stopped.
+    pub fn run<I, T, F>(&self, items: &[I], job: F) -> Vec<Result<T, Failure>>
+    where
+        I: Sync,
+        T: Send,
+        F: Fn(&I, &Database) -> T + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<_>>());
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(items.len()) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    let mut cancellation = Cancellation::new(self.token.clone());
+                    if let Some(timeout) = self.timeout {
+                        cancellation = cancellation.with_timeout(timeout);
+                    }
+                    let result = catch_unwind(AssertUnwindSafe(|| {
+                        cancellation.run(|| job(item, &self.database))
+                    }));
+                    let result = match result {
+                        Ok(result) => result.map_err(Failure::from),
+                        Err(payload) => Err(Failure::Error(panic_message(payload))),
+                    };
+                    results.lock().unwrap()[index] = Some(result);
+                });
+            }
+        });
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect()
+    }
+
+    /// Evaluates every query with [`Query::evaluate`].
+    pub fn evaluate_all(&self, queries: &[Query]) -> Vec<Result<Table, Failure>> {
+        self.run(queries, |query, database| query.evaluate(database))
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "query evaluation panicked".to_string(),
+        },
+    }
+}
@@ -0,0 +1,90 @@
+use crate::data_structure::database::Database;
+use crate::data_structure::join_tree::JoinTree;
+use crate::data_structure::query::{Atom, Query, Term};
+use crate::data_structure::statistics::join_selectivity;
+
+/// Estimated number of tuples of `atom` once its constants and repeated variables are applied to
+/// its relation in `database`, from the statistics of the relation.
+pub fn atom_cardinality(atom: &Atom, database: &Database) -> f64 {
+    let statistics = database.statistics(&atom.relation_name);
+    let rows = statistics.rows as f64;
+    let mut estimate = rows;
+    for (index, term) in atom.terms.iter().enumerate() {
+        match term {
+            Term::Constant(value) => estimate *= statistics.equality_selectivity(index, value),
+            Term::Variable(_) => {
+                if let Some(first) = atom.terms[..index].iter().position(|t| t == term) {
+                    estimate *= statistics.column_equality_selectivity(first, index);
+                }
+            }
+        }
+    }
+    // a selection keeps at least one tuple of a non-empty relation
+    if rows > 0.0 {
+        estimate.max(1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Estimated number of tuples of the join of `left`, estimated at `left_rows` tuples, with
+/// `right`, estimated at `right_rows`: every shared variable keeps the fraction of the pairs given
+/// by the distinct counts of its columns.
+pub fn join_cardinality(
+    left: &Atom,
+    left_rows: f64,
+    right: &Atom,
+    right_rows: f64,
+    database: &Database,
+) -> f64 {
+    let left_statistics = database.statistics(&left.relation_name);
+    let right_statistics = database.statistics(&right.relation_name);
+    let mut estimate = left_rows * right_rows;
+    for variable in left.shared_variables(right) {
+        let position = |atom: &Atom| atom.terms.iter().position(|term| *term == variable);
+        if let (Some(l), Some(r)) = (position(left), position(right)) {
+            estimate *= join_selectivity(&left_statistics.columns[l], &right_statistics.columns[r]);
+        }
+    }
+    estimate
+}
+
+/// Estimated cost of running Yannakakis' algorithm for `query` along `join_tree`: the number of
+/// tuples read and produced by the joins from the leaves up to the root. The semi-join passes read
+/// every relation twice whatever the tree, so only the join phase tells trees apart.
+///
+/// Every node joins with the result of each of its children and keeps its own variables and the
+/// head variables. A child whose subtree holds no head variable only filters the node, which keeps
+/// its size; otherwise the size of the result is estimated by [`join_cardinality`].
+pub fn join_tree_cost(query: &Query, join_tree: &JoinTree, database: &Database) -> f64 {
+    subtree_cost(query, join_tree, join_tree.get_root(), database).1
+}
+
+/// Estimated size of the result computed for `node` and the cost of computing it.
+fn subtree_cost(
+    query: &Query,
+    join_tree: &JoinTree,
+    node: &Atom,
+    database: &Database,
+) -> (f64, f64) {
+    let mut size = atom_cardinality(node, database);
+    let mut cost = 0.0;
+    for child in join_tree.get_children(node) {
+        let (child_size, child_cost) = subtree_cost(query, join_tree, child, database);
+        cost += child_cost + size + child_size;
+        if carries_head_variables(query, join_tree, node, child) {
+            size = join_cardinality(node, size, child, child_size, database);
+        }
+        cost += size;
+    }
+    (size, cost)
+}
+
+/// Whether the subtree of `child` holds head variables that `node` does not have.
+fn carries_head_variables(query: &Query, join_tree: &JoinTree, node: &Atom, child: &Atom) -> bool {
+    let mut subtree = join_tree.get_descendants(child);
+    subtree.push(child);
+    query.head.variables().iter().any(|variable| {
+        !node.terms.contains(variable) && subtree.iter().any(|atom| atom.terms.contains(variable))
+    })
+}
@@ -1,75 +1,183 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Display, Formatter};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use arrow::record_batch::RecordBatch;
 use arrow_schema::{Field, Schema};
 
-use crate::data_structure::query::Query;
+use crate::data_structure::index::{Index, IndexKind};
 use crate::data_structure::query::Term::{Constant, Variable};
+use crate::data_structure::query::{Query, Term};
+use crate::data_structure::statistics::TableStatistics;
 use crate::data_structure::table::Table;
 
-#[derive(Clone)]
+/// A catalog of relations. Tables are shared behind `Arc`s, and their columns are themselves
+/// reference-counted Arrow arrays, so cloning a database or deriving a renamed view of it never
+/// copies any data. The statistics of a table, computed the first time they are needed, and the
+/// indexes created on it are kept until the table changes.
+#[derive(Clone, Default)]
 pub struct Database {
-    pub beers: Table,
-    pub breweries: Table,
-    pub categories: Table,
-    pub locations: Table,
-    pub styles: Table,
+    tables: BTreeMap<String, Arc<Table>>,
+    /// Shared by the clones and the views of the database, so that statistics computed through
+    /// any of them are computed once.
+    statistics: BTreeMap<String, Arc<OnceLock<Arc<TableStatistics>>>>,
+    indexes: BTreeMap<String, Vec<Arc<Index>>>,
+    /// Relations computed by Datalog programs rather than loaded.
+    derived: BTreeSet<String>,
 }
 
 impl Display for Database {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}\n{}\n{}\n{}\n{}",
-            self.beers, self.breweries, self.categories, self.locations, self.styles
-        )
+        let tables = self
+            .tables
+            .values()
+            .map(|table| table.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}", tables.join("\n"))
     }
 }
 
 impl Database {
     pub fn rename(&mut self, query: &Query) {
+        *self = self.view(query);
+    }
+
+    /// The database with the relations of `query` renamed after the terms of its atoms. Only the
+    /// schemas change: the renamed tables share their columns with this database, and keep their
+    /// statistics and indexes.
+    pub fn view(&self, query: &Query) -> Database {
+        let mut database = self.clone();
         for atom in query.body.iter() {
-            let table = self.get_table(&atom.relation_name);
-            let mut new_table = table.clone();
-            let mut new_field: Vec<Field> = vec![];
-            for (index, term) in atom.terms.iter().enumerate() {
-                match term {
-                    Variable(name) => {
-                        let field = table.get_data().schema().field(index).clone();
-                        new_field.push(field.clone().with_name(name));
-                    }
-                    Constant(_) => {
-                        new_field.push(table.get_data().schema().field(index).clone());
-                    }
+            let table = Self::rename_table(self.get_table(&atom.relation_name), &atom.terms);
+            database.tables.insert(table.name.clone(), Arc::new(table));
+        }
+        database
+    }
+
+    /// Builds the working database of `aliased`, a copy of `query` whose body atoms may have been
+    /// given distinct relation names: every alias gets its own copy of the relation it stands for,
+    /// with the columns renamed after the terms of its atom.
+    pub fn bind(&self, query: &Query, aliased: &Query) -> Database {
+        let mut database = Database::default();
+        for (atom, alias) in query.body.iter().zip(aliased.body.iter()) {
+            let mut table = Self::rename_table(self.get_table(&atom.relation_name), &atom.terms);
+            table.set_name(&alias.relation_name);
+            database.add_table(table);
+            if let Some(statistics) = self.statistics.get(&atom.relation_name) {
+                database
+                    .statistics
+                    .insert(alias.relation_name.clone(), statistics.clone());
+            }
+            if let Some(indexes) = self.indexes.get(&atom.relation_name) {
+                database
+                    .indexes
+                    .insert(alias.relation_name.clone(), indexes.clone());
+            }
+        }
+        database
+    }
+
+    fn rename_table(table: &Table, terms: &[Term]) -> Table {
+        let mut new_table = table.clone();
+        let mut new_field: Vec<Field> = vec![];
+        for (index, term) in terms.iter().enumerate() {
+            match term {
+                Variable(name) => {
+                    let field = table.get_data().schema().field(index).clone();
+                    new_field.push(field.clone().with_name(name));
+                }
+                Constant(_) => {
+                    new_field.push(table.get_data().schema().field(index).clone());
                 }
             }
-            let schema = Schema::new(new_field);
-            let new_columns = table.get_data().columns().to_vec();
-            new_table.set_data(RecordBatch::try_new(Arc::new(schema), new_columns).unwrap());
-            self.set_table(&atom.relation_name, new_table);
         }
+        let schema = Schema::new(new_field);
+        let new_columns = table.get_data().columns().to_vec();
+        new_table.set_data(RecordBatch::try_new(Arc::new(schema), new_columns).unwrap());
+        new_table
+    }
+
+    /// Registers `table` under its own name, replacing any relation with the same name.
+    pub fn add_table(&mut self, table: Table) {
+        self.derived.remove(&table.name);
+        self.statistics.insert(table.name.clone(), Arc::default());
+        self.indexes.remove(&table.name);
+        self.tables.insert(table.name.clone(), Arc::new(table));
+    }
+
+    /// Registers `table` as a relation computed by a program, which a later program may compute
+    /// again.
+    pub fn add_derived_table(&mut self, table: Table) {
+        let name = table.name.clone();
+        self.add_table(table);
+        self.derived.insert(name);
+    }
+
+    pub fn is_derived(&self, name: &str) -> bool {
+        self.derived.contains(name)
     }
 
     pub fn set_table(&mut self, table_name: &str, table: Table) {
-        match table_name {
-            "beers" => self.beers.data = table.get_data(),
-            "breweries" => self.breweries.data = table.get_data(),
-            "categories" => self.categories.data = table.get_data(),
-            "locations" => self.locations.data = table.get_data(),
-            "styles" => self.styles.data = table.get_data(),
-            _ => panic!("Table not found"),
+        self.statistics
+            .insert(table_name.to_string(), Arc::default());
+        self.indexes.remove(table_name);
+        match self.tables.get_mut(table_name) {
+            Some(existing) => Arc::make_mut(existing).data = table.get_data(),
+            None => panic!("Table not found"),
         }
     }
 
     pub fn get_table(&self, name: &str) -> &Table {
-        match name {
-            "beers" => &self.beers,
-            "breweries" => &self.breweries,
-            "categories" => &self.categories,
-            "locations" => &self.locations,
-            "styles" => &self.styles,
-            _ => panic!("Table not found"),
+        match self.tables.get(name) {
+            Some(table) => table,
+            None => panic!("Table not found"),
         }
     }
+
+    /// Computes and keeps the statistics of every table.
+    pub fn analyze(&mut self) {
+        for name in self.tables.keys() {
+            self.statistics(name);
+        }
+    }
+
+    /// The statistics of a table, computed on demand if it has not been analyzed.
+    pub fn statistics(&self, name: &str) -> Arc<TableStatistics> {
+        let table = self.get_table(name);
+        self.statistics[name]
+            .get_or_init(|| Arc::new(table.statistics()))
+            .clone()
+    }
+
+    /// Builds an index of `kind` on a column of a table, replacing any index on that column.
+    pub fn create_index(&mut self, name: &str, column: usize, kind: IndexKind) {
+        let index = Arc::new(Index::new(self.get_table(name), column, kind));
+        let indexes = self.indexes.entry(name.to_string()).or_default();
+        indexes.retain(|existing| existing.column() != column);
+        indexes.push(index);
+    }
+
+    pub fn drop_indexes(&mut self, name: &str) {
+        self.indexes.remove(name);
+    }
+
+    /// The indexes created on a table, which selections and semi-joins on it use.
+    pub fn indexes(&self, name: &str) -> &[Arc<Index>] {
+        self.indexes.get(name).map_or(&[], |indexes| indexes)
+    }
+
+    /// The names of the columns of a relation, if it is in the database.
+    pub fn column_names(&self, name: &str) -> Option<Vec<String>> {
+        let table = self.tables.get(name)?;
+        let schema = table.data.schema();
+        Some(schema.fields().iter().map(|f| f.name().clone()).collect())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tables.contains_key(name)
+    }
+
+    pub fn table_names(&self) -> Vec<String> {
+        self.tables.keys().cloned().collect()
+    }
 }
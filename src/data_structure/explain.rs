@@ -0,0 +1,335 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::data_structure::cost;
+use crate::data_structure::database::Database;
+use crate::data_structure::join_tree::JoinTree;
+use crate::data_structure::profile::Profile;
+use crate::data_structure::query::{Atom, Query, Term};
+use crate::data_structure::table::Table;
+
+/// How [`Query::evaluate`] evaluates a query, worked out without evaluating it: join trees are
+/// chosen from the sizes of the relations alone.
+#[derive(Clone, Debug)]
+pub struct Plan {
+    /// The positive part of the query as it is evaluated: redundant atoms removed, repeated
+    /// relations aliased, and the head made of the variables needed by the rest of the plan.
+    pub query: Query,
+    /// Body atoms removed because the rest of the body already implies them.
+    pub removed: Vec<Atom>,
+    pub strategy: Strategy,
+    /// Negated atoms, whose matches are removed from the answers by anti-joins.
+    pub anti_joins: Vec<Atom>,
+    /// The terms the answers are laid out on at the end, constants included.
+    pub head: Vec<Term>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Strategy {
+    /// Yannakakis' algorithm on each connected component of the body, whose results are then
+    /// combined by a cartesian product.
+    Yannakakis(Vec<ComponentPlan>),
+    /// The atoms joined one after the other, in body order.
+    LeftDeepJoin { reason: Fallback, order: Vec<Atom> },
+}
+
+/// Why a query is not evaluated with Yannakakis' algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fallback {
+    Cyclic,
+    Boolean,
+    EmptyBody,
+}
+
+impl fmt::Display for Fallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fallback::Cyclic => write!(f, "the body is cyclic"),
+            Fallback::Boolean => write!(f, "the query is boolean"),
+            Fallback::EmptyBody => write!(f, "the body is empty"),
+        }
+    }
+}
+
+/// Yannakakis' algorithm along the join tree of one connected component.
+#[derive(Clone, Debug)]
+pub struct ComponentPlan {
+    /// The component, with the head variables occurring in it.
+    pub query: Query,
+    pub join_tree: JoinTree,
+    /// Estimated cost of the join phase (see [`cost::join_tree_cost`]).
+    pub cost: f64,
+    /// Estimated number of tuples of every node once its constants and repeated variables are
+    /// applied (see [`cost::atom_cardinality`]), in pre-order.
+    pub cardinalities: Vec<(Atom, f64)>,
+    /// The semi-joins of the bottom-up pass, in order, as `(reduced, by)` pairs.
+    pub post_order: Vec<(Atom, Atom)>,
+    /// The semi-joins of the top-down pass. Components without head variables are only checked
+    /// to have a match, so they have no top-down pass and no join phase.
+    pub pre_order: Vec<(Atom, Atom)>,
+    pub joins: Vec<JoinStep>,
+}
+
+/// A join of the join phase: a node with the result of the subtree of one of its children.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JoinStep {
+    /// The node, with the columns it has before the join.
+    pub node: Atom,
+    /// The child, with the columns of the result of its subtree.
+    pub child: Atom,
+    /// The variables joined on.
+    pub on: Vec<Term>,
+    /// The columns the node keeps after the join.
+    pub projection: Vec<Term>,
+}
+
+impl ComponentPlan {
+    pub(crate) fn new(query: Query, join_tree: JoinTree, database: &Database) -> Self {
+        let cost = cost::join_tree_cost(&query, &join_tree, database);
+        let cardinalities = join_tree
+            .pre_order()
+            .map(|node| (node.clone(), cost::atom_cardinality(node, database)))
+            .collect();
+        let mut post_order = vec![];
+        for node in join_tree.post_order() {
+            for child in join_tree.get_children(node) {
+                post_order.push((node.clone(), child.clone()));
+            }
+        }
+        let mut pre_order = vec![];
+        let mut joins = vec![];
+        if !query.is_boolean() {
+            for node in join_tree.pre_order() {
+                for child in join_tree.get_children(node) {
+                    pre_order.push((child.clone(), node.clone()));
+                }
+            }
+            join_steps(&query, &join_tree, join_tree.get_root(), &mut joins);
+        }
+        ComponentPlan {
+            query,
+            join_tree,
+            cost,
+            cardinalities,
+            post_order,
+            pre_order,
+            joins,
+        }
+    }
+}
+
+/// Appends the join steps below `node`, children first, and returns the columns of its result.
+fn join_steps(query: &Query, join_tree: &JoinTree, node: &Atom, steps: &mut Vec<JoinStep>) -> Atom {
+    let children = join_tree
+        .get_children(node)
+        .into_iter()
+        .map(|child| join_steps(query, join_tree, child, steps))
+        .collect::<Vec<_>>();
+    let mut atom = Atom {
+        relation_name: node.relation_name.clone(),
+        terms: node.variables(),
+    };
+    for child in children {
+        let projection = Atom::union(&atom, &child)
+            .into_iter()
+            .filter(|term| node.terms.contains(term) || query.head.terms.contains(term))
+            .collect::<Vec<_>>();
+        steps.push(JoinStep {
+            on: atom.shared_variables(&child),
+            node: atom.clone(),
+            child,
+            projection: projection.clone(),
+        });
+        atom.terms = projection;
+    }
+    atom
+}
+
+fn terms(terms: &[Term]) -> String {
+    let terms = terms
+        .iter()
+        .map(|term| term.to_string())
+        .collect::<Vec<_>>();
+    terms.join(", ")
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "query: {}", self.query)?;
+        if !self.removed.is_empty() {
+            let removed = self.removed.iter().map(|atom| atom.to_string());
+            writeln!(
+                f,
+                "removed as redundant: {}",
+                removed.collect::<Vec<_>>().join(", ")
+            )?;
+        }
+        match &self.strategy {
+            Strategy::Yannakakis(components) => {
+                writeln!(
+                    f,
+                    "strategy: Yannakakis on {} component(s)",
+                    components.len()
+                )?;
+                for (index, component) in components.iter().enumerate() {
+                    writeln!(f, "component {}: {}", index + 1, component.query)?;
+                    write!(f, "{}", component)?;
+                }
+                let products = components
+                    .iter()
+                    .filter(|component| !component.query.is_boolean())
+                    .count();
+                if products > 1 {
+                    writeln!(f, "cartesian product of the components")?;
+                }
+            }
+            Strategy::LeftDeepJoin { reason, order } => {
+                writeln!(f, "strategy: left-deep join, as {}", reason)?;
+                let mut joined: Option<Atom> = None;
+                for atom in order {
+                    let variables = Atom {
+                        relation_name: atom.relation_name.clone(),
+                        terms: atom.variables(),
+                    };
+                    match joined {
+                        None => writeln!(f, "  scan {}", atom)?,
+                        Some(ref left) => writeln!(
+                            f,
+                            "  join {} on [{}]",
+                            atom,
+                            terms(&left.shared_variables(&variables))
+                        )?,
+                    }
+                    joined = Some(match joined {
+                        None => variables,
+                        Some(left) => Atom {
+                            terms: Atom::union(&left, &variables),
+                            ..left
+                        },
+                    });
+                }
+                writeln!(f, "  project on [{}]", terms(&self.query.head.terms))?;
+            }
+        }
+        for atom in &self.anti_joins {
+            writeln!(f, "anti-join with {}", atom)?;
+        }
+        write!(f, "answers: [{}]", terms(&self.head))
+    }
+}
+
+impl fmt::Display for ComponentPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "  join tree rooted at {} (estimated cost {:.1}):",
+            self.join_tree.get_root(),
+            self.cost
+        )?;
+        for (parent, child) in self.join_tree.edges() {
+            writeln!(f, "    {} -> {}", parent, child)?;
+        }
+        writeln!(f, "  estimated rows after selection:")?;
+        for (node, rows) in &self.cardinalities {
+            writeln!(f, "    {}: {:.1}", node, rows)?;
+        }
+        writeln!(f, "  semi-join pass (post-order):")?;
+        for (reduced, by) in &self.post_order {
+            writeln!(f, "    {} semi-join {}", reduced, by)?;
+        }
+        if self.query.is_boolean() {
+            return writeln!(f, "  checked for a match at the root");
+        }
+        writeln!(f, "  semi-join pass (pre-order):")?;
+        for (reduced, by) in &self.pre_order {
+            writeln!(f, "    {} semi-join {}", reduced, by)?;
+        }
+        writeln!(f, "  join phase:")?;
+        for step in &self.joins {
+            writeln!(
+                f,
+                "    {} join {} on [{}], project on [{}]",
+                step.node,
+                step.child,
+                terms(&step.on),
+                terms(&step.projection)
+            )?;
+        }
+        writeln!(
+            f,
+            "  project on [{}], distinct",
+            terms(&self.query.head.terms)
+        )
+    }
+}
+
+/// A plan together with what its operators did when the query was evaluated.
+#[derive(Clone)]
+pub struct Analysis {
+    pub plan: Plan,
+    pub profile: Profile,
+    pub answers: Table,
+    pub time: Duration,
+}
+
+impl Analysis {
+    /// Writes `node`, the statistics of its operators and then its children, indented by `depth`.
+    fn write_node(
+        &self,
+        f: &mut fmt::Formatter,
+        join_tree: &JoinTree,
+        node: &Atom,
+        depth: usize,
+    ) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        writeln!(f, "{}{}", indent, node)?;
+        for (phase, operator, stats) in self.profile.node(&node.relation_name) {
+            writeln!(f, "{}  [{}] {}: {}", indent, phase, operator, stats)?;
+        }
+        for child in join_tree.get_children(node) {
+            self.write_node(f, join_tree, child, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// The plan as a tree of join-tree nodes, each with the statistics of its operators by phase.
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "query: {}", self.plan.query)?;
+        match &self.plan.strategy {
+            Strategy::Yannakakis(components) => {
+                writeln!(
+                    f,
+                    "strategy: Yannakakis on {} component(s)",
+                    components.len()
+                )?;
+                for (index, component) in components.iter().enumerate() {
+                    writeln!(
+                        f,
+                        "component {} (estimated cost {:.1}):",
+                        index + 1,
+                        component.cost
+                    )?;
+                    let join_tree = &component.join_tree;
+                    self.write_node(f, join_tree, join_tree.get_root(), 1)?;
+                }
+            }
+            Strategy::LeftDeepJoin { reason, order } => {
+                writeln!(f, "strategy: left-deep join, as {}", reason)?;
+                for atom in order {
+                    self.write_node(f, &JoinTree::single(atom.clone()), atom, 1)?;
+                }
+            }
+        }
+        for atom in &self.plan.anti_joins {
+            writeln!(f, "anti-join with {}", atom)?;
+        }
+        write!(
+            f,
+            "answers: {} rows in {:.3} ms",
+            self.answers.get_data().num_rows(),
+            self.time.as_secs_f64() * 1000.0
+        )
+    }
+}
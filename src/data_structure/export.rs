@@ -0,0 +1,48 @@
+use crate::data_structure::query::Atom;
+
+/// `value` as a JSON string literal.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut escaped = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// `values` as a JSON array of strings.
+pub(crate) fn json_strings(values: &[String]) -> String {
+    let values = values
+        .iter()
+        .map(|value| json_string(value))
+        .collect::<Vec<_>>();
+    format!("[{}]", values.join(","))
+}
+
+/// `atom` as a JSON object with its relation, its terms and its label.
+pub(crate) fn atom_json(atom: &Atom) -> String {
+    let terms = atom
+        .terms
+        .iter()
+        .map(|term| term.to_string())
+        .collect::<Vec<_>>();
+    format!(
+        "{{\"relation\":{},\"terms\":{},\"label\":{}}}",
+        json_string(&atom.relation_name),
+        json_strings(&terms),
+        json_string(&atom.to_string())
+    )
+}
+
+/// `value` as a DOT quoted string.
+pub(crate) fn dot_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
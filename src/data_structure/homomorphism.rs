@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use crate::data_structure::database::Database;
+use crate::data_structure::profile;
+use crate::data_structure::query::{Atom, Query, Term};
+use crate::data_structure::table::Table;
+
+/// A mapping of variables to terms. Constants are always mapped to themselves.
+pub type Homomorphism = HashMap<Term, Term>;
+
+/// Searches a homomorphism that extends `fixed` and sends every atom of `from` onto an atom of
+/// `to`: an atom of the same relation whose terms are the images of its own terms.
+pub fn find_homomorphism(from: &[Atom], to: &[Atom], fixed: Homomorphism) -> Option<Homomorphism> {
+    // atoms with few candidate images first, so dead ends are found early
+    let mut atoms = from.iter().collect::<Vec<_>>();
+    atoms.sort_by_key(|atom| candidates(atom, to).count());
+    extend(&atoms, to, fixed)
+}
+
+fn candidates<'a>(atom: &'a Atom, to: &'a [Atom]) -> impl Iterator<Item = &'a Atom> + 'a {
+    to.iter().filter(move |image| {
+        image.relation_name == atom.relation_name && image.terms.len() == atom.terms.len()
+    })
+}
+
+fn extend(atoms: &[&Atom], to: &[Atom], mapping: Homomorphism) -> Option<Homomorphism> {
+    let Some((atom, rest)) = atoms.split_first() else {
+        return Some(mapping);
+    };
+    for image in candidates(atom, to) {
+        if let Some(extended) = map_atom(atom, image, &mapping) {
+            if let Some(result) = extend(rest, to, extended) {
+                return Some(result);
+            }
+        }
+    }
+    None
+}
+
+/// Extends `mapping` so that `atom` is sent onto `image`, if the two are compatible.
+fn map_atom(atom: &Atom, image: &Atom, mapping: &Homomorphism) -> Option<Homomorphism> {
+    let mut mapping = mapping.clone();
+    for (term, target) in atom.terms.iter().zip(&image.terms) {
+        match term {
+            Term::Constant(_) if term != target => return None,
+            Term::Constant(_) => {}
+            Term::Variable(_) => match mapping.get(term) {
+                Some(mapped) if mapped != target => return None,
+                Some(_) => {}
+                None => {
+                    mapping.insert(term.clone(), target.clone());
+                }
+            },
+        }
+    }
+    Some(mapping)
+}
+
+/// The identity on the variables of `atom`, used to keep head variables in place.
+pub fn identity(atom: &Atom) -> Homomorphism {
+    atom.variables()
+        .into_iter()
+        .map(|variable| (variable.clone(), variable))
+        .collect()
+}
+
+/// Decides whether `query` is contained in `other`, i.e. whether on every database each answer of
+/// `query` is also an answer of `other`. By the homomorphism theorem this holds exactly when
+/// `other` maps onto `query` with its head sent onto the head of `query`. When `other` is acyclic
+/// that mapping is found in polynomial time by running Yannakakis' algorithm for `other` on the
+/// canonical database of `query`; otherwise it is searched for directly. Queries with negated
+/// atoms are never reported as contained.
+pub fn is_contained(query: &Query, other: &Query) -> bool {
+    if !query.negated.is_empty() || !other.negated.is_empty() {
+        return false;
+    }
+    if query.head.terms.len() != other.head.terms.len() {
+        return false;
+    }
+    let mut fixed = Homomorphism::new();
+    let head = Atom {
+        relation_name: other.head.relation_name.clone(),
+        terms: other.head.terms.clone(),
+    };
+    let target = Atom {
+        relation_name: other.head.relation_name.clone(),
+        terms: query.head.terms.clone(),
+    };
+    match map_atom(&head, &target, &fixed) {
+        Some(mapping) => fixed = mapping,
+        None => return false,
+    }
+    if other.is_acyclic() {
+        holds_on_canonical_database(query, other, &fixed)
+    } else {
+        find_homomorphism(&other.body, &query.body, fixed).is_some()
+    }
+}
+
+/// Whether `other`, its head variables mapped by `fixed`, has an answer on the canonical database
+/// of `query`, where every variable is frozen into a constant of its own.
+fn holds_on_canonical_database(query: &Query, other: &Query, fixed: &Homomorphism) -> bool {
+    let database = canonical_database(&query.body);
+    for atom in &other.body {
+        if !database.contains(&atom.relation_name)
+            || database
+                .get_table(&atom.relation_name)
+                .get_data()
+                .num_columns()
+                != atom.terms.len()
+        {
+            return false;
+        }
+    }
+    let body = other
+        .body
+        .iter()
+        .map(|atom| Atom {
+            relation_name: atom.relation_name.clone(),
+            terms: atom
+                .terms
+                .iter()
+                .map(|term| match fixed.get(term) {
+                    Some(image) => Term::Constant(freeze(image)),
+                    None => term.clone(),
+                })
+                .collect(),
+        })
+        .collect();
+    let boolean = Query {
+        head: Atom {
+            relation_name: other.head.relation_name.clone(),
+            terms: vec![],
+        },
+        body,
+        negated: vec![],
+    };
+    // not part of the evaluation of any query, so never profiled
+    profile::without(|| boolean.holds(&database))
+}
+
+fn canonical_database(body: &[Atom]) -> Database {
+    let mut database = Database::default();
+    let mut relations: Vec<&String> = vec![];
+    for atom in body {
+        if !relations.contains(&&atom.relation_name) {
+            relations.push(&atom.relation_name);
+        }
+    }
+    for relation in relations {
+        let atoms = body
+            .iter()
+            .filter(|atom| atom.relation_name == *relation)
+            .collect::<Vec<_>>();
+        let arity = atoms[0].terms.len();
+        let rows = atoms
+            .iter()
+            .filter(|atom| atom.terms.len() == arity)
+            .map(|atom| atom.terms.iter().map(freeze).collect())
+            .collect::<Vec<Vec<String>>>();
+        let columns = (0..arity).map(|i| format!("c{}", i)).collect::<Vec<_>>();
+        database.add_table(Table::from_rows(relation.clone(), &columns, &rows));
+    }
+    database
+}
+
+/// Constants stay themselves; variables become a value no constant of a query can be equal to.
+fn freeze(term: &Term) -> String {
+    match term {
+        Term::Constant(constant) => constant.clone(),
+        Term::Variable(name) => format!("\u{0}{}", name),
+    }
+}
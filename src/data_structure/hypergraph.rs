@@ -1,24 +1,54 @@
 use std::collections::{HashMap, HashSet};
 
+use crate::data_structure::export::{atom_json, dot_string, json_strings};
 use crate::data_structure::query::{Atom, Query, Term};
 
 #[derive(Clone, Debug)]
 pub struct Hypergraph {
     pub hyperedges: HashMap<Atom, HashSet<Term>>,
+    /// The atoms in body order, which is the order ears and witnesses are searched in.
+    order: Vec<Atom>,
 }
 
 impl Hypergraph {
     pub fn new(query: &Query) -> Self {
         let mut edges = HashMap::new();
+        let mut order = vec![];
         for atom in &query.body {
-            let variables = atom.terms.iter().cloned().collect();
+            if !edges.contains_key(atom) {
+                order.push(atom.clone());
+            }
+            // anonymous variables occur in a single atom and cannot make hyperedges overlap
+            let variables = atom
+                .terms
+                .iter()
+                .filter(|term| !term.is_anonymous())
+                .cloned()
+                .collect();
             edges.insert(atom.clone(), variables);
         }
-        Hypergraph { hyperedges: edges }
+        Hypergraph {
+            hyperedges: edges,
+            order,
+        }
+    }
+
+    /// The hyperedges in body order.
+    fn ordered(&self) -> impl Iterator<Item = (&Atom, &HashSet<Term>)> {
+        self.order
+            .iter()
+            .filter_map(|atom| self.hyperedges.get_key_value(atom))
+    }
+
+    pub fn remove(&mut self, atom: &Atom) {
+        self.hyperedges.remove(atom);
+        self.order.retain(|other| other != atom);
     }
 
+    /// Finds the first ear in body order, with the first witness in body order, so that the join
+    /// tree built from successive ears only depends on the query.
     pub fn find_ear(&self) -> Option<(Atom, Atom)> {
-        for (ear_candidate, ear_vertices) in &self.hyperedges {
+        for (ear_candidate, ear_vertices) in self.ordered() {
             // all vertices of the hyperedge are exclusive to that hyperedge
             if self.is_vertices_exclusive(ear_candidate, ear_vertices) {
                 return Some((ear_candidate.clone(), ear_candidate.clone()));
@@ -33,7 +63,7 @@ impl Hypergraph {
                     witness_vertices.insert(vertex.clone());
                 }
             }
-            for (witness_candidate, witness_candidate_vertices) in &self.hyperedges {
+            for (witness_candidate, witness_candidate_vertices) in self.ordered() {
                 if ear_candidate != witness_candidate
                     && witness_vertices.is_subset(witness_candidate_vertices)
                 {
@@ -44,12 +74,26 @@ impl Hypergraph {
         None
     }
 
+    /// Every hyperedge that can serve as a witness of `ear`, in body order: those containing all
+    /// the vertices `ear` shares with other hyperedges.
+    pub fn find_witnesses(&self, ear: &Atom) -> Vec<Atom> {
+        let shared = self.hyperedges[ear]
+            .iter()
+            .filter(|vertex| !self.is_vertex_exclusive(ear, vertex))
+            .cloned()
+            .collect::<HashSet<_>>();
+        self.ordered()
+            .filter(|(witness, vertices)| *witness != ear && shared.is_subset(vertices))
+            .map(|(witness, _)| witness.clone())
+            .collect()
+    }
+
     pub fn is_acyclic(&self) -> bool {
         match self.find_ear() {
             // If an ear is found, remove it and check the remaining hypergraph
             Some((ear, _)) => {
                 let mut remaining_hypergraph = self.clone();
-                remaining_hypergraph.hyperedges.remove(&ear);
+                remaining_hypergraph.remove(&ear);
                 remaining_hypergraph.is_acyclic()
             }
             // If no ear is found, terminate and check the hypergraph is empty
@@ -82,4 +126,93 @@ impl Hypergraph {
     pub fn is_empty(&self) -> bool {
         self.hyperedges.is_empty()
     }
+
+    /// Hyperedges in a stable order, and the pairs of them that share variables.
+    fn sorted(&self) -> (Vec<&Atom>, Vec<(usize, usize)>) {
+        let mut atoms = self.hyperedges.keys().collect::<Vec<_>>();
+        atoms.sort_by_key(|atom| atom.to_string());
+        let mut overlaps = vec![];
+        for left in 0..atoms.len() {
+            for right in left + 1..atoms.len() {
+                if !self.shared_variables(atoms[left], atoms[right]).is_empty() {
+                    overlaps.push((left, right));
+                }
+            }
+        }
+        (atoms, overlaps)
+    }
+
+    /// The variables two hyperedges share, leaving out the constants they both hold.
+    fn shared_variables(&self, left: &Atom, right: &Atom) -> Vec<String> {
+        let mut shared = self.hyperedges[left]
+            .intersection(&self.hyperedges[right])
+            .filter(|term| matches!(term, Term::Variable(_)))
+            .map(|term| term.to_string())
+            .collect::<Vec<_>>();
+        shared.sort();
+        shared
+    }
+
+    /// The hypergraph in Graphviz DOT: one node per hyperedge labeled with its atom, and an
+    /// undirected edge between every two hyperedges sharing variables, labeled with them.
+    pub fn to_dot(&self) -> String {
+        let (atoms, overlaps) = self.sorted();
+        let mut dot = String::from("graph hypergraph {\n    node [shape=box];\n");
+        for (index, atom) in atoms.iter().enumerate() {
+            dot.push_str(&format!(
+                "    n{} [label={}];\n",
+                index,
+                dot_string(&atom.to_string())
+            ));
+        }
+        for (left, right) in overlaps {
+            let shared = self.shared_variables(atoms[left], atoms[right]);
+            dot.push_str(&format!(
+                "    n{} -- n{} [label={}];\n",
+                left,
+                right,
+                dot_string(&shared.join(","))
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The hypergraph as JSON: the hyperedges with their atom and vertices, and the overlapping
+    /// pairs of hyperedges sharing variables with those variables, referred to by index in
+    /// `hyperedges`.
+    pub fn to_json(&self) -> String {
+        let (atoms, overlaps) = self.sorted();
+        let hyperedges = atoms
+            .iter()
+            .map(|atom| {
+                let mut vertices = self.hyperedges[*atom]
+                    .iter()
+                    .map(|term| term.to_string())
+                    .collect::<Vec<_>>();
+                vertices.sort();
+                format!(
+                    "{{\"atom\":{},\"vertices\":{}}}",
+                    atom_json(atom),
+                    json_strings(&vertices)
+                )
+            })
+            .collect::<Vec<_>>();
+        let overlaps = overlaps
+            .iter()
+            .map(|(left, right)| {
+                format!(
+                    "{{\"left\":{},\"right\":{},\"shared\":{}}}",
+                    left,
+                    right,
+                    json_strings(&self.shared_variables(atoms[*left], atoms[*right]))
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"hyperedges\":[{}],\"edges\":[{}]}}",
+            hyperedges.join(","),
+            overlaps.join(",")
+        )
+    }
 }
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use arrow::array::{Array, ArrayRef, BooleanArray, StringArray};
+use arrow::compute::cast;
+use arrow_row::{RowConverter, Rows, SortField};
+
+use crate::data_structure::interrupt::{self, CHECK_ROWS};
+use crate::data_structure::parallel;
+use crate::data_structure::table::Table;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexKind {
+    /// The rows grouped by value.
+    Hash,
+    /// The rows ordered by value, in the order of the type of the column.
+    Sorted,
+}
+
+impl fmt::Display for IndexKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IndexKind::Hash => write!(f, "hash"),
+            IndexKind::Sorted => write!(f, "sorted"),
+        }
+    }
+}
+
+/// A secondary index on one column of a table, which finds the rows holding a value without
+/// scanning the column. Values are compared in the row format of the type of the column, so that
+/// constants, which are strings, are cast to that type first.
+///
+/// An index only applies to the tables sharing the very column it was built on, such as the
+/// renamed views of its table, and never to a table derived from it by selection or join.
+pub struct Index {
+    column: usize,
+    array: ArrayRef,
+    lookup: Lookup,
+    lookups: AtomicUsize,
+}
+
+enum Lookup {
+    /// The indices of the rows, in table order, by the row format of their value.
+    Hash(HashMap<Box<[u8]>, Vec<u32>>),
+    /// The row format of the values, and the indices of the rows ordered by it, ties in table
+    /// order.
+    Sorted { keys: Rows, order: Vec<u32> },
+}
+
+impl Index {
+    pub fn new(table: &Table, column: usize, kind: IndexKind) -> Self {
+        let array = table.data.column(column).clone();
+        let keys = converter(&array)
+            .convert_columns(std::slice::from_ref(&array))
+            .unwrap();
+        let lookup = match kind {
+            IndexKind::Hash => {
+                let mut buckets: HashMap<Box<[u8]>, Vec<u32>> = HashMap::new();
+                for (index, key) in keys.iter().enumerate() {
+                    if array.is_valid(index) {
+                        buckets
+                            .entry(key.as_ref().into())
+                            .or_default()
+                            .push(index as u32);
+                    }
+                }
+                Lookup::Hash(buckets)
+            }
+            IndexKind::Sorted => {
+                let mut order = (0..keys.num_rows() as u32)
+                    .filter(|index| array.is_valid(*index as usize))
+                    .collect::<Vec<_>>();
+                order.sort_by(|a, b| keys.row(*a as usize).cmp(&keys.row(*b as usize)));
+                Lookup::Sorted { keys, order }
+            }
+        };
+        Index {
+            column,
+            array,
+            lookup,
+            lookups: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn kind(&self) -> IndexKind {
+        match self.lookup {
+            Lookup::Hash(_) => IndexKind::Hash,
+            Lookup::Sorted { .. } => IndexKind::Sorted,
+        }
+    }
+
+    /// How many times the index was looked up, by selections and semi-joins.
+    pub fn lookups(&self) -> usize {
+        self.lookups.load(Ordering::Relaxed)
+    }
+
+    /// Whether the column of `table` the index is on is the one it was built on.
+    pub fn covers(&self, table: &Table) -> bool {
+        self.column < table.data.num_columns()
+            && table
+                .data
+                .column(self.column)
+                .to_data()
+                .ptr_eq(&self.array.to_data())
+    }
+
+    /// The indices of the rows holding `value`, in table order.
+    pub fn lookup(&self, value: &str) -> Vec<u32> {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        let Some(keys) = self.keys(&StringArray::from(vec![value])) else {
+            return vec![];
+        };
+        let key = keys.row(0);
+        match &self.lookup {
+            Lookup::Hash(buckets) => buckets.get(key.as_ref()).cloned().unwrap_or_default(),
+            Lookup::Sorted { keys, order } => {
+                let start = order.partition_point(|index| keys.row(*index as usize) < key);
+                let end = order.partition_point(|index| keys.row(*index as usize) <= key);
+                let mut rows = order[start..end].to_vec();
+                rows.sort();
+                rows
+            }
+        }
+    }
+
+    /// Whether every value of `values` occurs in the column.
+    pub(crate) fn matches(&self, values: &dyn Array) -> BooleanArray {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        let Some(probe) = self.keys(values) else {
+            return BooleanArray::from(vec![false; values.len()]);
+        };
+        let parts = parallel::map(&parallel::partitions(probe.num_rows()), |range| {
+            range
+                .clone()
+                .map(|row| {
+                    if row % CHECK_ROWS == 0 {
+                        interrupt::check();
+                    }
+                    let key = probe.row(row);
+                    let found = match &self.lookup {
+                        Lookup::Hash(buckets) => buckets.contains_key(key.as_ref()),
+                        Lookup::Sorted { keys, order } => order
+                            .binary_search_by(|index| keys.row(*index as usize).cmp(&key))
+                            .is_ok(),
+                    };
+                    Some(values.is_valid(row) && found)
+                })
+                .collect::<Vec<_>>()
+        });
+        parts.concat().into_iter().collect()
+    }
+
+    /// The row format of `values` cast to the type of the column, if they can be.
+    fn keys(&self, values: &dyn Array) -> Option<Rows> {
+        let values = cast(values, self.array.data_type()).ok()?;
+        Some(converter(&self.array).convert_columns(&[values]).unwrap())
+    }
+}
+
+fn converter(array: &ArrayRef) -> RowConverter {
+    RowConverter::new(vec![SortField::new(array.data_type().clone())]).unwrap()
+}
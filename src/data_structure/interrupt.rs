@@ -0,0 +1,136 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::data_structure::scope;
+
+/// Rows processed by a join loop between two checks.
+pub(crate) const CHECK_ROWS: usize = 4096;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Cancellation>> = const { RefCell::new(None) };
+}
+
+/// Why an evaluation stopped before its end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interrupted {
+    Cancelled,
+    Timeout,
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Interrupted::Cancelled => write!(f, "cancelled"),
+            Interrupted::Timeout => write!(f, "timed out"),
+        }
+    }
+}
+
+/// A flag shared by all its clones. Cancelling one cancels the evaluations of every clone.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// What an evaluation is stopped by: a token, and optionally a deadline.
+#[derive(Clone, Debug, Default)]
+pub struct Cancellation {
+    token: CancellationToken,
+    deadline: Option<Instant>,
+}
+
+impl Cancellation {
+    pub fn new(token: CancellationToken) -> Self {
+        Cancellation {
+            token,
+            deadline: None,
+        }
+    }
+
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline `timeout` from now.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn check(&self) -> Result<(), Interrupted> {
+        if self.token.is_cancelled() {
+            return Err(Interrupted::Cancelled);
+        }
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(Interrupted::Timeout),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs `f`, typically an evaluation, stopping it at the first check made after the token is
+    /// cancelled or the deadline has passed. Checks are made between the steps of a join tree and
+    /// every [`CHECK_ROWS`] rows inside join loops, on all the threads the evaluation uses. They
+    /// stop it by unwinding, so with `panic = "abort"` only the check before `f` starts is made
+    /// and `f` always runs to its end.
+    pub fn run<T>(&self, f: impl FnOnce() -> T) -> Result<T, Interrupted> {
+        self.check()?;
+        match catch_unwind(AssertUnwindSafe(|| with(Some(self.clone()), f))) {
+            Ok(result) => Ok(result),
+            Err(payload) => match payload.downcast::<Interrupted>() {
+                Ok(interrupted) => Err(*interrupted),
+                Err(payload) => resume_unwind(payload),
+            },
+        }
+    }
+}
+
+/// Stops the evaluation running on this thread if its cancellation says so, by unwinding up to
+/// [`Cancellation::run`]. Does nothing outside of it, nor in builds with `panic = "abort"`, where
+/// unwinding would abort the process instead.
+pub(crate) fn check() {
+    if cfg!(panic = "abort") {
+        return;
+    }
+    let interrupted = CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .and_then(|cancellation| cancellation.check().err())
+    });
+    if let Some(interrupted) = interrupted {
+        // unlike a panic, this does not report anything on its way up
+        resume_unwind(Box::new(interrupted));
+    }
+}
+
+/// The cancellation of this thread, to hand over to the threads it spawns.
+pub(crate) fn current() -> Option<Cancellation> {
+    scope::get(&CURRENT)
+}
+
+/// Runs `f` with `cancellation` as the one of this thread.
+pub(crate) fn with<T>(cancellation: Option<Cancellation>, f: impl FnOnce() -> T) -> T {
+    scope::with(&CURRENT, cancellation, f)
+}
@@ -1,100 +1,283 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
+use crate::data_structure::export::{atom_json, dot_string, json_strings};
 use crate::data_structure::query::Atom;
 
-#[derive(Clone, Debug)]
+/// A rooted tree over the atoms of a query, stored as an arena: atoms are numbered in the order
+/// they are added, and every node knows its parent and its direct children, so that moving around
+/// the tree never scans it.
+#[derive(Clone, Debug, Default, Eq)]
 pub struct JoinTree {
-    edges: HashSet<(Atom, Atom)>,
+    /// The atoms of the tree, in the order they were added. A tree may be a single atom.
+    nodes: Vec<Atom>,
+    /// Position of every atom in `nodes`.
+    index: HashMap<Atom, usize>,
+    parents: Vec<Option<usize>>,
+    /// Direct children of every node, in the order their edges were added.
+    children: Vec<Vec<usize>>,
 }
 
-impl Default for JoinTree {
-    fn default() -> Self {
-        Self::new()
+/// Two join trees are the same when they have the same nodes and every node has the same parent,
+/// whatever order they were added in.
+impl PartialEq for JoinTree {
+    fn eq(&self, other: &Self) -> bool {
+        self.nodes.len() == other.nodes.len()
+            && self.nodes.iter().all(|node| {
+                other.index.contains_key(node) && self.get_parent(node) == other.get_parent(node)
+            })
     }
 }
 
 impl JoinTree {
-    fn new() -> Self {
-        JoinTree {
-            edges: HashSet::new(),
-        }
+    /// The tree made of `atom` alone.
+    pub fn single(atom: Atom) -> Self {
+        let mut join_tree = JoinTree::default();
+        join_tree.add_node(atom);
+        join_tree
     }
 
-    pub fn get_root(&self) -> Atom {
-        self.edges
-            .clone()
-            .into_iter()
-            .find(|(parent, _)| self.get_parent(parent).is_none())
-            .unwrap()
-            .0
+    fn root_index(&self) -> Option<usize> {
+        self.parents.iter().position(|parent| parent.is_none())
     }
 
-    pub fn add_edge(&mut self, ear: Atom, witness: Atom) {
-        self.edges.insert((ear, witness));
+    pub fn get_root(&self) -> &Atom {
+        &self.nodes[self.root_index().unwrap()]
     }
 
-    pub fn get_parent(&self, child: &Atom) -> Option<Atom> {
-        for (parent, child_check) in &self.edges {
-            if child == child_check {
-                return Some(parent.clone());
-            }
+    pub fn add_node(&mut self, atom: Atom) -> usize {
+        if let Some(index) = self.index.get(&atom) {
+            return *index;
         }
-        None
+        self.index.insert(atom.clone(), self.nodes.len());
+        self.nodes.push(atom);
+        self.parents.push(None);
+        self.children.push(vec![]);
+        self.nodes.len() - 1
     }
 
-    pub fn get_nodes(&self) -> HashSet<Atom> {
-        let mut nodes = HashSet::new();
-        for (parent, child) in &self.edges {
-            nodes.insert(parent.clone());
-            nodes.insert(child.clone());
+    /// Hangs `child` from `parent`, adding either of them that is not a node yet.
+    pub fn add_edge(&mut self, parent: Atom, child: Atom) {
+        let parent = self.add_node(parent);
+        let child = self.add_node(child);
+        if self.parents[child] != Some(parent) {
+            self.parents[child] = Some(parent);
+            self.children[parent].push(child);
         }
-        nodes
     }
 
-    pub fn get_children(&self, parent: &Atom) -> HashSet<Atom> {
-        let mut children = HashSet::new();
-        for (parent_check, child) in &self.edges {
-            if parent == parent_check {
-                children.insert(child.clone());
-                children.extend(self.get_children(child));
+    /// The same tree hanging from `root` instead: the edges on the path from `root` up to the
+    /// current root are reversed. Any node of a join tree can be its root, since the join tree
+    /// property does not depend on the direction of the edges. Returns `None` when `root` is not a
+    /// node of the tree.
+    pub fn with_root(&self, root: &Atom) -> Option<JoinTree> {
+        let root = *self.index.get(root)?;
+        let mut join_tree = JoinTree::default();
+        for node in &self.nodes {
+            join_tree.add_node(node.clone());
+        }
+        let mut stack = vec![(root, None)];
+        while let Some((node, from)) = stack.pop() {
+            let neighbours = self.parents[node].iter().chain(&self.children[node]);
+            for &neighbour in neighbours.filter(|neighbour| Some(**neighbour) != from) {
+                join_tree.add_edge(self.nodes[node].clone(), self.nodes[neighbour].clone());
+                stack.push((neighbour, Some(node)));
             }
         }
-        children
+        Some(join_tree)
     }
 
-    pub fn find_node_with_no_child_in_nodes(&self, nodes: &HashSet<Atom>) -> Option<Atom> {
-        let Some(node) = nodes.iter().find(|node| {
-            let child_nodes = self.get_children(node);
-            child_nodes.iter().all(|child| !nodes.contains(child))
-        }) else {
-            return None;
+    pub fn get_parent(&self, child: &Atom) -> Option<&Atom> {
+        let parent = self.parents[*self.index.get(child)?]?;
+        Some(&self.nodes[parent])
+    }
+
+    /// The atoms of the tree, in the order they were added.
+    pub fn get_nodes(&self) -> &[Atom] {
+        &self.nodes
+    }
+
+    pub fn contains(&self, atom: &Atom) -> bool {
+        self.index.contains_key(atom)
+    }
+
+    /// The direct children of `parent`.
+    pub fn get_children(&self, parent: &Atom) -> Vec<&Atom> {
+        match self.index.get(parent) {
+            Some(parent) => self.children[*parent]
+                .iter()
+                .map(|child| &self.nodes[*child])
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Every node below `parent`, in pre-order.
+    pub fn get_descendants(&self, parent: &Atom) -> Vec<&Atom> {
+        match self.index.get(parent) {
+            Some(parent) => self
+                .pre_order_from(*parent)
+                .into_iter()
+                .skip(1)
+                .map(|node| &self.nodes[node])
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn is_leaf(&self, node: &Atom) -> bool {
+        self.index
+            .get(node)
+            .is_some_and(|node| self.children[*node].is_empty())
+    }
+
+    /// The nodes, every parent before its children, children in order.
+    pub fn pre_order(&self) -> impl Iterator<Item = &Atom> {
+        let order = match self.root_index() {
+            Some(root) => self.pre_order_from(root),
+            None => vec![],
         };
-        Some(node.clone())
+        order.into_iter().map(|node| &self.nodes[node])
+    }
+
+    /// The nodes, every parent after its children, children in order.
+    pub fn post_order(&self) -> impl Iterator<Item = &Atom> {
+        let mut order = vec![];
+        let mut stack = self.root_index().into_iter().collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            order.push(node);
+            stack.extend(&self.children[node]);
+        }
+        order.into_iter().rev().map(|node| &self.nodes[node])
     }
 
-    pub fn find_node_with_no_parent_in_nodes(&self, nodes: &HashSet<Atom>) -> Option<Atom> {
-        let Some(node) = nodes.iter().find(|node| {
-            let parent_node = self.get_parent(node);
-            match parent_node {
-                Some(parent) => !nodes.contains(&parent),
-                None => true,
+    fn pre_order_from(&self, node: usize) -> Vec<usize> {
+        let mut order = vec![];
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            order.push(node);
+            stack.extend(self.children[node].iter().rev());
+        }
+        order
+    }
+
+    /// Parent-child pairs, in pre-order of the children.
+    pub fn edges(&self) -> impl Iterator<Item = (&Atom, &Atom)> {
+        self.pre_order()
+            .filter_map(|child| Some((self.get_parent(child)?, child)))
+    }
+
+    /// Nodes and edges in a stable order, for the exports.
+    fn sorted(&self) -> (Vec<Atom>, Vec<(usize, usize)>) {
+        let mut nodes = self.nodes.clone();
+        nodes.sort_by_key(|node| node.to_string());
+        let index = |atom: &Atom| nodes.iter().position(|node| node == atom).unwrap();
+        let mut edges = self
+            .edges()
+            .map(|(parent, child)| (index(parent), index(child)))
+            .collect::<Vec<_>>();
+        edges.sort();
+        (nodes, edges)
+    }
+
+    /// The tree in Graphviz DOT: one node per atom labeled with its terms, the root in bold, and
+    /// parent-to-child edges labeled with the variables the two atoms share.
+    pub fn to_dot(&self) -> String {
+        let (nodes, edges) = self.sorted();
+        let root = (!self.nodes.is_empty()).then(|| self.get_root());
+        let mut dot = String::from("digraph join_tree {\n    node [shape=box];\n");
+        for (index, node) in nodes.iter().enumerate() {
+            let style = if Some(node) == root {
+                ", style=bold"
+            } else {
+                ""
+            };
+            dot.push_str(&format!(
+                "    n{} [label={}{}];\n",
+                index,
+                dot_string(&node.to_string()),
+                style
+            ));
+        }
+        for (parent, child) in edges {
+            dot.push_str(&format!(
+                "    n{} -> n{} [label={}];\n",
+                parent,
+                child,
+                dot_string(&shared_label(&nodes[parent], &nodes[child]))
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The tree as JSON: the root, the nodes with their relation and terms, and the edges with the
+    /// variables shared by parent and child. Nodes are referred to by their index in `nodes`.
+    pub fn to_json(&self) -> String {
+        let (nodes, edges) = self.sorted();
+        let root = match self.nodes.is_empty() {
+            true => "null".to_string(),
+            false => {
+                let root = self.get_root();
+                nodes
+                    .iter()
+                    .position(|node| node == root)
+                    .unwrap()
+                    .to_string()
             }
-        }) else {
-            return None;
         };
-        Some(node.clone())
+        let nodes_json = nodes.iter().map(atom_json).collect::<Vec<_>>();
+        let edges_json = edges
+            .iter()
+            .map(|(parent, child)| {
+                format!(
+                    "{{\"parent\":{},\"child\":{},\"shared\":{}}}",
+                    parent,
+                    child,
+                    json_strings(&shared(&nodes[*parent], &nodes[*child]))
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"root\":{},\"nodes\":[{}],\"edges\":[{}]}}",
+            root,
+            nodes_json.join(","),
+            edges_json.join(",")
+        )
     }
+}
 
-    pub fn is_leaf(&self, node: &Atom) -> bool {
-        self.get_children(node).is_empty()
+/// One join tree per connected component of a query body. Components share no variable, so each
+/// is evaluated on its own and the results are combined by a cartesian product.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct JoinForest {
+    pub trees: Vec<JoinTree>,
+}
+
+impl JoinForest {
+    pub fn get_nodes(&self) -> HashSet<&Atom> {
+        self.trees
+            .iter()
+            .flat_map(|tree| tree.get_nodes())
+            .collect()
     }
 }
 
+pub(crate) fn shared(left: &Atom, right: &Atom) -> Vec<String> {
+    left.shared_variables(right)
+        .iter()
+        .map(|term| term.to_string())
+        .collect()
+}
+
+pub(crate) fn shared_label(left: &Atom, right: &Atom) -> String {
+    shared(left, right).join(",")
+}
+
 impl fmt::Display for JoinTree {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut result = String::new();
-        for (parent, child) in &self.edges {
+        for (parent, child) in self.edges() {
             result.push_str(&format!(
                 "{} -> {}\n",
                 parent.relation_name, child.relation_name
@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+static USED: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets how many bytes the hash tables of joins and deduplications and the tables they are built
+/// on may take at once, across all threads. `None` removes the limit. Operators that would go over
+/// it spill their input to disk instead, and read it back in blocks that fit.
+pub fn set_limit(bytes: Option<usize>) {
+    LIMIT.store(bytes.unwrap_or(usize::MAX), Ordering::SeqCst);
+}
+
+pub fn limit() -> Option<usize> {
+    match LIMIT.load(Ordering::SeqCst) {
+        usize::MAX => None,
+        bytes => Some(bytes),
+    }
+}
+
+/// Bytes currently reserved by running operators.
+pub fn used() -> usize {
+    USED.load(Ordering::SeqCst)
+}
+
+/// A share of the memory budget, given back when dropped.
+#[derive(Debug, Default)]
+pub struct Reservation {
+    size: usize,
+}
+
+impl Reservation {
+    pub fn new() -> Self {
+        Reservation::default()
+    }
+
+    /// Reserves `bytes` more, unless that would go over the limit.
+    pub fn try_grow(&mut self, bytes: usize) -> bool {
+        let limit = LIMIT.load(Ordering::SeqCst);
+        let grown = USED.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+            used.checked_add(bytes).filter(|total| *total <= limit)
+        });
+        if grown.is_ok() {
+            self.size += bytes;
+        }
+        grown.is_ok()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        USED.fetch_sub(self.size, Ordering::SeqCst);
+    }
+}
@@ -1,8 +1,23 @@
+pub mod cost;
 pub mod database;
+pub mod explain;
+mod export;
+pub mod homomorphism;
 pub mod hypergraph;
+pub mod index;
+pub mod interrupt;
 pub mod join_tree;
+pub mod memory;
+mod parallel;
 pub mod parser;
+pub mod profile;
+pub mod program;
 pub mod query;
 pub mod reader;
 mod relational_algebra;
-mod table;
+mod scope;
+mod spill;
+pub mod sql;
+pub mod statistics;
+pub mod stream;
+pub mod table;
@@ -0,0 +1,106 @@
+use std::ops::Range;
+use std::panic::resume_unwind;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::data_structure::{interrupt, profile};
+
+/// Inputs with fewer rows than this are not worth splitting across threads.
+pub(crate) const PARTITION_ROWS: usize = 1 << 16;
+
+/// The threads spawned by [`map`] still running, in the whole process.
+static SPAWNED: AtomicUsize = AtomicUsize::new(0);
+
+/// A thread of [`map`] counted in [`SPAWNED`] until it is dropped.
+struct Slot;
+
+impl Slot {
+    /// A slot if fewer threads than available cores are running.
+    fn acquire() -> Option<Slot> {
+        SPAWNED
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |spawned| {
+                (spawned < threads()).then_some(spawned + 1)
+            })
+            .ok()
+            .map(|_| Slot)
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        SPAWNED.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// An item of [`map`] on a thread of its own, or on the calling thread.
+enum Task<H, R> {
+    Spawned(H),
+    Inline(R),
+}
+
+/// Applies `f` to every item in parallel, spawning at most one thread per available core in the
+/// whole process; items that find none free run on the calling thread. Results are in the order
+/// of the items.
+pub(crate) fn map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let Some((last, rest)) = items.split_last() else {
+        return vec![];
+    };
+    if rest.is_empty() {
+        return vec![f(last)];
+    }
+    let cancellation = interrupt::current();
+    let context = profile::current();
+    thread::scope(|scope| {
+        let tasks = rest
+            .iter()
+            .map(|item| match Slot::acquire() {
+                Some(slot) => Task::Spawned(scope.spawn(|| {
+                    let _slot = slot;
+                    interrupt::with(cancellation.clone(), || {
+                        profile::with(context.clone(), || f(item))
+                    })
+                })),
+                None => Task::Inline(item),
+            })
+            .collect::<Vec<_>>();
+        // the items left to the calling thread run while the spawned ones do
+        let tasks = tasks
+            .into_iter()
+            .map(|task| match task {
+                Task::Spawned(handle) => Task::Spawned(handle),
+                Task::Inline(item) => Task::Inline(f(item)),
+            })
+            .collect::<Vec<_>>();
+        let last = f(last);
+        let mut results = tasks
+            .into_iter()
+            .map(|task| match task {
+                Task::Spawned(handle) => handle
+                    .join()
+                    .unwrap_or_else(|payload| resume_unwind(payload)),
+                Task::Inline(result) => result,
+            })
+            .collect::<Vec<_>>();
+        results.push(last);
+        results
+    })
+}
+
+/// Splits `rows` rows into consecutive ranges, one per available core but none smaller than
+/// [`PARTITION_ROWS`].
+pub(crate) fn partitions(rows: usize) -> Vec<Range<usize>> {
+    let size = rows.div_ceil(threads()).max(PARTITION_ROWS);
+    (0..rows)
+        .step_by(size)
+        .map(|start| start..(start + size).min(rows))
+        .collect()
+}
+
+fn threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
@@ -1,42 +1,185 @@
 use nom::branch::alt;
 use nom::bytes::complete::{tag, take_while, take_while1};
-use nom::combinator::map;
-use nom::multi::separated_list0;
-use nom::sequence::{delimited, tuple};
+use nom::character::complete::{multispace0, multispace1, satisfy};
+use nom::combinator::{map, recognize};
+use nom::error::{Error, ErrorKind};
+use nom::multi::{separated_list0, separated_list1};
+use nom::sequence::{delimited, preceded, tuple};
 use nom::IResult;
 
+use crate::data_structure::database::Database;
+use crate::data_structure::program::{head_column_names, Program};
 use crate::data_structure::query::{Atom, Query, Term};
 
+/// A name starting with a letter or digit, followed by letters, digits and underscores.
+fn parse_identifier(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((
+        satisfy(|c: char| c.is_alphanumeric()),
+        take_while(|c: char| c.is_alphanumeric() || c == '_'),
+    )))(input)
+}
+
 fn parse_variable(input: &str) -> IResult<&str, Term> {
-    map(take_while1(|c: char| c.is_alphanumeric()), |s: &str| {
-        Term::Variable(s.to_string())
-    })(input)
+    map(parse_identifier, |s: &str| Term::Variable(s.to_string()))(input)
+}
+
+/// A string delimited by `quote`. Inside it, the quote is written twice or escaped with a
+/// backslash, and `\\`, `\n`, `\t`, `\r` and `\u{...}` escapes are understood.
+fn parse_quoted(quote: char) -> impl Fn(&str) -> IResult<&str, String> {
+    move |input: &str| {
+        let error = || nom::Err::Error(Error::new(input, ErrorKind::Escaped));
+        let rest = input.strip_prefix(quote).ok_or_else(error)?;
+        let mut value = String::new();
+        let mut chars = rest.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                c if c == quote => {
+                    if chars.next_if(|(_, next)| *next == quote).is_none() {
+                        return Ok((&rest[index + 1..], value));
+                    }
+                    value.push(quote);
+                }
+                '\\' => match chars.next().ok_or_else(error)?.1 {
+                    'n' => value.push('\n'),
+                    't' => value.push('\t'),
+                    'r' => value.push('\r'),
+                    'u' => {
+                        if chars.next().map(|(_, c)| c) != Some('{') {
+                            return Err(error());
+                        }
+                        let mut hex = String::new();
+                        loop {
+                            match chars.next().ok_or_else(error)?.1 {
+                                '}' => break,
+                                digit => hex.push(digit),
+                            }
+                        }
+                        let code = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+                        value.push(code.ok_or_else(error)?);
+                    }
+                    escaped => value.push(escaped),
+                },
+                c => value.push(c),
+            }
+        }
+        Err(error())
+    }
 }
 
+/// A constant, between single or double quotes.
 fn parse_constant(input: &str) -> IResult<&str, Term> {
-    map(
-        delimited(tag("'"), take_while(|c: char| c != '\''), tag("'")),
-        |s: &str| Term::Constant(s.to_string()),
-    )(input)
+    map(alt((parse_quoted('\''), parse_quoted('"'))), Term::Constant)(input)
+}
+
+/// A relation name. Unquoted names are case-insensitive and read in lowercase; names between
+/// double quotes keep their case.
+fn parse_relation_name(input: &str) -> IResult<&str, String> {
+    alt((
+        parse_quoted('"'),
+        map(parse_identifier, |name: &str| name.to_lowercase()),
+    ))(input)
+}
+
+/// `_`, a variable occurring nowhere else, renamed once the whole query is parsed.
+fn parse_anonymous_variable(input: &str) -> IResult<&str, Term> {
+    map(tag("_"), |_| Term::Variable("_".to_string()))(input)
 }
 
 fn parse_term(input: &str) -> IResult<&str, Term> {
-    alt((parse_constant, parse_variable))(input)
+    alt((parse_constant, parse_anonymous_variable, parse_variable))(input)
+}
+
+/// A comma, followed by any whitespace.
+fn parse_separator(input: &str) -> IResult<&str, (&str, &str)> {
+    tuple((tag(","), multispace0))(input)
 }
 
 fn parse_terms(input: &str) -> IResult<&str, Vec<Term>> {
-    separated_list0(tag(","), parse_term)(input)
+    separated_list0(parse_separator, parse_term)(input)
 }
 
-fn parse_atom(input: &str) -> IResult<&str, Atom> {
+/// `column: term`, binding a term to a column of the relation by name.
+fn parse_named_term(input: &str) -> IResult<&str, (String, Term)> {
     map(
         tuple((
-            take_while(|c: char| c.is_alphanumeric()),
-            delimited(tag("("), parse_terms, tag(")")),
+            take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+            multispace0,
+            tag(":"),
+            multispace0,
+            parse_term,
         )),
-        |(relation_name, terms)| Atom {
-            relation_name: relation_name.to_string().to_lowercase(),
+        |(column, _, _, _, term)| (column.to_lowercase(), term),
+    )(input)
+}
+
+fn parse_named_terms(input: &str) -> IResult<&str, Vec<(String, Term)>> {
+    separated_list1(parse_separator, parse_named_term)(input)
+}
+
+/// An atom as written, whose named terms are placed once the schema of its relation is known.
+struct ParsedAtom {
+    relation_name: String,
+    terms: Vec<Term>,
+    columns: Option<Vec<String>>,
+}
+
+impl ParsedAtom {
+    /// Places named terms at the position of their column, and `_` everywhere else.
+    fn resolve(self, columns_of: &ColumnLookup) -> Result<Atom, String> {
+        let Some(columns) = self.columns else {
+            return Ok(Atom {
+                relation_name: self.relation_name,
+                terms: self.terms,
+            });
+        };
+        let Some(schema) = columns_of(&self.relation_name) else {
+            return Err(format!("unknown relation {}", self.relation_name));
+        };
+        let mut terms = vec![Term::Variable("_".to_string()); schema.len()];
+        let mut named: Vec<&String> = vec![];
+        for (column, term) in columns.iter().zip(self.terms) {
+            let Some(index) = schema.iter().position(|name| name == column) else {
+                return Err(format!("{} has no column {}", self.relation_name, column));
+            };
+            if named.contains(&column) {
+                return Err(format!(
+                    "column {} of {} is named twice",
+                    column, self.relation_name
+                ));
+            }
+            named.push(column);
+            terms[index] = term;
+        }
+        Ok(Atom {
+            relation_name: self.relation_name,
             terms,
+        })
+    }
+}
+
+/// Column names of a relation, if it is known.
+type ColumnLookup<'a> = dyn Fn(&str) -> Option<Vec<String>> + 'a;
+
+fn parse_atom(input: &str) -> IResult<&str, ParsedAtom> {
+    map(
+        tuple((
+            parse_relation_name,
+            delimited(
+                tag("("),
+                alt((
+                    map(parse_named_terms, |named| {
+                        let (columns, terms) = named.into_iter().unzip();
+                        (terms, Some(columns))
+                    }),
+                    map(parse_terms, |terms| (terms, None)),
+                )),
+                tag(")"),
+            ),
+        )),
+        |(relation_name, (terms, columns))| ParsedAtom {
+            relation_name,
+            terms,
+            columns,
         },
     )(input)
 }
@@ -44,33 +187,84 @@ fn parse_atom(input: &str) -> IResult<&str, Atom> {
 fn parse_head(input: &str) -> IResult<&str, Atom> {
     map(
         tuple((
-            tag("Answer"),
+            parse_relation_name,
             delimited(tag("("), parse_terms, tag(")")),
             tag(":-"),
         )),
-        |(name, terms, _)| Atom {
-            relation_name: name.to_string().to_lowercase(),
+        |(relation_name, terms, _)| Atom {
+            relation_name,
             terms,
         },
     )(input)
 }
 
-fn parse_body(input: &str) -> IResult<&str, Vec<Atom>> {
+/// A body literal: an atom, or a negated atom written `not Atom(...)`.
+fn parse_literal(input: &str) -> IResult<&str, (bool, ParsedAtom)> {
+    alt((
+        map(
+            preceded(tuple((tag("not"), multispace1)), parse_atom),
+            |atom| (true, atom),
+        ),
+        map(parse_atom, |atom| (false, atom)),
+    ))(input)
+}
+
+fn parse_body(input: &str) -> IResult<&str, Vec<(bool, ParsedAtom)>> {
     map(
-        tuple((separated_list0(tag(","), parse_atom), tag("."))),
-        |(atoms, _)| atoms,
+        tuple((separated_list0(parse_separator, parse_literal), tag("."))),
+        |(literals, _)| literals,
     )(input)
 }
 
+/// Parses a query whose atoms give their terms by position. Named terms need the schemas of a
+/// catalog: see [`try_parse_query`].
 pub fn parse_query(input: &str) -> Query {
-    let Ok((_, result)) = map(tuple((parse_head, parse_body)), |(head, body)| Query {
+    match try_parse_query(input, &Database::default()) {
+        Ok(query) => query,
+        Err(error) => panic!("Error parsing query: {}: {}", input, error),
+    }
+}
+
+/// Parses a query, resolving named terms against the relations of `catalog`.
+pub fn try_parse_query(input: &str, catalog: &Database) -> Result<Query, String> {
+    parse_rule(input, &|relation_name| catalog.column_names(relation_name))
+}
+
+fn parse_rule(input: &str, columns_of: &ColumnLookup) -> Result<Query, String> {
+    let Ok((_, (head, literals))) = tuple((parse_head, parse_body))(input) else {
+        return Err("syntax error".to_string());
+    };
+    let mut query = Query {
         head,
-        body,
-    })(input) else {
-        panic!("Error parsing query: {}", input);
+        body: vec![],
+        negated: vec![],
     };
+    for (negated, atom) in literals {
+        let atom = atom.resolve(columns_of)?;
+        if negated {
+            query.negated.push(atom);
+        } else {
+            query.body.push(atom);
+        }
+    }
+    Ok(name_anonymous_variables(query))
+}
 
-    result
+/// Renames every `_` to `_1`, `_2`, ..., which cannot clash with the variables of the query.
+fn name_anonymous_variables(mut query: Query) -> Query {
+    let mut count = 0;
+    let atoms = std::iter::once(&mut query.head)
+        .chain(query.body.iter_mut())
+        .chain(query.negated.iter_mut());
+    for atom in atoms {
+        for term in atom.terms.iter_mut() {
+            if *term == Term::Variable("_".to_string()) {
+                count += 1;
+                *term = Term::Variable(format!("_{}", count));
+            }
+        }
+    }
+    query
 }
 
 pub fn parse_queries(path: &str) -> Vec<Query> {
@@ -87,3 +281,29 @@ pub fn parse_queries(path: &str) -> Vec<Query> {
     }
     queries
 }
+
+/// Parses a Datalog program, one rule per line. Heads may define new predicates, which the
+/// bodies of other rules can then refer to, by position or by the names of their columns. The
+/// other relations are those of `catalog`, including the predicates of an earlier program.
+pub fn parse_program(input: &str, catalog: &Database) -> Program {
+    let lines = input.lines().filter(|line| !line.is_empty());
+    let heads = lines
+        .clone()
+        .filter_map(|line| parse_head(line).ok())
+        .map(|(_, head)| head)
+        .collect::<Vec<_>>();
+    let columns = |relation_name: &str| match heads
+        .iter()
+        .find(|head| head.relation_name == relation_name)
+    {
+        Some(head) => Some(head_column_names(head)),
+        None => catalog.column_names(relation_name),
+    };
+    let rules = lines
+        .map(|line| match parse_rule(line, &columns) {
+            Ok(rule) => rule,
+            Err(error) => panic!("Error parsing query: {}: {}", line, error),
+        })
+        .collect();
+    Program::new(rules)
+}
@@ -0,0 +1,189 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::data_structure::query::Atom;
+use crate::data_structure::scope;
+use crate::data_structure::table::Table;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+/// The phases of Yannakakis' algorithm. Bodies joined atom by atom only have a join phase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    /// The bottom-up semi-join pass.
+    PostOrder,
+    /// The top-down semi-join pass.
+    PreOrder,
+    Join,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Phase::PostOrder => write!(f, "post-order"),
+            Phase::PreOrder => write!(f, "pre-order"),
+            Phase::Join => write!(f, "join"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Operator {
+    Select,
+    SemiJoin,
+    Join,
+    Project,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operator::Select => write!(f, "select"),
+            Operator::SemiJoin => write!(f, "semi-join"),
+            Operator::Join => write!(f, "join"),
+            Operator::Project => write!(f, "project"),
+        }
+    }
+}
+
+/// What the calls of an operator for one node in one phase added up to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub calls: usize,
+    pub rows_in: usize,
+    pub rows_out: usize,
+    pub time: Duration,
+    /// Bytes held by the outputs of the calls.
+    pub memory: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} call(s), {} -> {} rows, {:.3} ms, {} bytes",
+            self.calls,
+            self.rows_in,
+            self.rows_out,
+            self.time.as_secs_f64() * 1000.0,
+            self.memory
+        )
+    }
+}
+
+/// The statistics of the operators run during an evaluation, by the relation name of the
+/// join-tree node they ran for, phase and operator. Operators run inside another operator, such
+/// as the selection of a semi-join, are counted as part of it.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub stats: BTreeMap<(String, Phase, Operator), Stats>,
+}
+
+impl Profile {
+    pub fn get(&self, node: &str, phase: Phase, operator: Operator) -> Option<&Stats> {
+        self.stats.get(&(node.to_string(), phase, operator))
+    }
+
+    /// The statistics recorded for `node`, by phase and operator.
+    pub fn node<'a>(
+        &'a self,
+        node: &'a str,
+    ) -> impl Iterator<Item = (Phase, Operator, &'a Stats)> + 'a {
+        self.stats
+            .iter()
+            .filter(move |((name, _, _), _)| name == node)
+            .map(|((_, phase, operator), stats)| (*phase, *operator, stats))
+    }
+}
+
+/// Output of an operator, whose size and memory are recorded.
+pub(crate) trait Measured {
+    fn rows(&self) -> usize;
+    fn memory(&self) -> usize;
+}
+
+impl Measured for Table {
+    fn rows(&self) -> usize {
+        self.data.num_rows()
+    }
+
+    fn memory(&self) -> usize {
+        self.memory_size()
+    }
+}
+
+/// Where operators are being recorded: the profile, and the node and phase they run for.
+#[derive(Clone)]
+pub(crate) struct Context {
+    profile: Arc<Mutex<Profile>>,
+    node: Option<(String, Phase)>,
+    measuring: bool,
+}
+
+/// Runs `f` and records the statistics of the operators it runs.
+pub fn profile<T>(f: impl FnOnce() -> T) -> (T, Profile) {
+    let profile = Arc::new(Mutex::new(Profile::default()));
+    let context = Context {
+        profile: profile.clone(),
+        node: None,
+        measuring: false,
+    };
+    let result = with(Some(context), f);
+    let profile = profile.lock().unwrap().clone();
+    (result, profile)
+}
+
+/// Runs `f`, recording its operators for `node` in `phase` when profiling.
+pub(crate) fn at<T>(phase: Phase, node: &Atom, f: impl FnOnce() -> T) -> T {
+    match current() {
+        Some(context) => {
+            let node = Some((node.relation_name.clone(), phase));
+            with(Some(Context { node, ..context }), f)
+        }
+        None => f(),
+    }
+}
+
+/// Runs `f` without recording anything, for evaluations that are not part of the plan.
+pub(crate) fn without<T>(f: impl FnOnce() -> T) -> T {
+    with(None, f)
+}
+
+/// Runs `operator` on `rows_in` input rows and records it for the current node and phase, unless
+/// it runs inside another operator.
+pub(crate) fn measure<T: Measured>(operator: Operator, rows_in: usize, f: impl FnOnce() -> T) -> T {
+    let Some(context) = current().filter(|context| context.node.is_some() && !context.measuring)
+    else {
+        return f();
+    };
+    let start = Instant::now();
+    let inner = Context {
+        measuring: true,
+        ..context.clone()
+    };
+    let output = with(Some(inner), f);
+    let (node, phase) = context.node.unwrap();
+    let mut profile = context.profile.lock().unwrap();
+    let stats = profile.stats.entry((node, phase, operator)).or_default();
+    stats.calls += 1;
+    stats.rows_in += rows_in;
+    stats.rows_out += output.rows();
+    stats.time += start.elapsed();
+    stats.memory += output.memory();
+    output
+}
+
+/// The context of this thread, to hand over to the threads it spawns and to stream operators.
+pub(crate) fn current() -> Option<Context> {
+    scope::get(&CURRENT)
+}
+
+/// Runs `f` with `context` as the one of this thread.
+pub(crate) fn with<T>(context: Option<Context>, f: impl FnOnce() -> T) -> T {
+    scope::with(&CURRENT, context, f)
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use log::info;
+
+use crate::data_structure::database::Database;
+use crate::data_structure::query::{Atom, Query, Term};
+use crate::data_structure::table::Table;
+
+/// A Datalog program. Every rule is a conjunctive query whose head defines a (possibly new)
+/// predicate; the predicates defined this way are the IDB predicates and can be used in the
+/// bodies of other rules, including their own, just like the base relations of the database.
+#[derive(Debug)]
+pub struct Program {
+    pub rules: Vec<Query>,
+}
+
+impl Program {
+    pub fn new(rules: Vec<Query>) -> Self {
+        Program { rules }
+    }
+
+    /// Names of the predicates defined by the program, in order of first definition.
+    pub fn idb_predicates(&self) -> Vec<String> {
+        let mut predicates: Vec<String> = vec![];
+        for rule in &self.rules {
+            if !predicates.contains(&rule.head.relation_name) {
+                predicates.push(rule.head.relation_name.clone());
+            }
+        }
+        predicates
+    }
+
+    pub fn rules_for(&self, predicate: &str) -> Vec<&Query> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.head.relation_name == predicate)
+            .collect()
+    }
+
+    /// IDB predicates used in the bodies of the rules defining `predicate`, negated or not.
+    pub fn dependencies(&self, predicate: &str) -> Vec<String> {
+        let idb = self.idb_predicates();
+        let mut dependencies: Vec<String> = vec![];
+        for rule in self.rules_for(predicate) {
+            for atom in rule.body.iter().chain(&rule.negated) {
+                if idb.contains(&atom.relation_name) && !dependencies.contains(&atom.relation_name)
+                {
+                    dependencies.push(atom.relation_name.clone());
+                }
+            }
+        }
+        dependencies
+    }
+
+    /// IDB predicates negated in the bodies of the rules defining `predicate`.
+    pub fn negative_dependencies(&self, predicate: &str) -> Vec<String> {
+        let idb = self.idb_predicates();
+        let mut dependencies: Vec<String> = vec![];
+        for rule in self.rules_for(predicate) {
+            for atom in &rule.negated {
+                if idb.contains(&atom.relation_name) && !dependencies.contains(&atom.relation_name)
+                {
+                    dependencies.push(atom.relation_name.clone());
+                }
+            }
+        }
+        dependencies
+    }
+
+    /// A program is stratifiable when no predicate depends negatively on a predicate of its own
+    /// stratum, i.e. there is no recursion through negation.
+    pub fn check_stratification(&self) -> Result<(), String> {
+        for stratum in self.strata() {
+            for predicate in &stratum {
+                for dependency in self.negative_dependencies(predicate) {
+                    if stratum.contains(&dependency) {
+                        return Err(format!(
+                            "program is not stratifiable: {} depends negatively on {} \
+                             through recursion",
+                            predicate, dependency
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Splits the IDB predicates into strata: sets of mutually recursive predicates, ordered so
+    /// that every stratum only depends on itself and on the strata before it.
+    pub fn strata(&self) -> Vec<Vec<String>> {
+        let mut builder = StrataBuilder::default();
+        for predicate in self.idb_predicates() {
+            if !builder.index.contains_key(&predicate) {
+                builder.visit(self, &predicate);
+            }
+        }
+        builder.strata
+    }
+
+    /// Whether the predicates of `stratum` are defined in terms of themselves. Negated atoms
+    /// always refer to earlier strata, so only positive atoms can make a stratum recursive.
+    pub fn is_recursive(&self, stratum: &[String]) -> bool {
+        stratum.len() > 1 || self.dependencies(&stratum[0]).contains(&stratum[0])
+    }
+
+    /// Checks that the program can be evaluated over `database`: IDB predicates do not redefine
+    /// base relations (relations an earlier program derived are replaced), every body atom refers
+    /// to a known relation with the right arity, rules are safe and negation is stratified.
+    pub fn validate(&self, database: &Database) -> Result<(), String> {
+        let idb = self.idb_predicates();
+        for predicate in &idb {
+            if database.contains(predicate) && !database.is_derived(predicate) {
+                return Err(format!("{} is a base relation of the database", predicate));
+            }
+        }
+        for rule in &self.rules {
+            let head = &rule.head;
+            if self.arity(&head.relation_name, database) != Some(head.terms.len()) {
+                return Err(format!(
+                    "{} is defined with different arities",
+                    head.relation_name
+                ));
+            }
+            for atom in rule.body.iter().chain(&rule.negated) {
+                match self.arity(&atom.relation_name, database) {
+                    None => return Err(format!("unknown relation {}", atom.relation_name)),
+                    Some(arity) if arity != atom.terms.len() => {
+                        return Err(format!(
+                            "{} expects {} terms, got {}",
+                            atom.relation_name,
+                            arity,
+                            atom.terms.len()
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+            rule.check_safety()?;
+        }
+        self.check_stratification()?;
+        Ok(())
+    }
+
+    fn arity(&self, predicate: &str, database: &Database) -> Option<usize> {
+        match self.rules_for(predicate).first() {
+            Some(rule) => Some(rule.head.terms.len()),
+            None if database.contains(predicate) => {
+                Some(database.get_table(predicate).get_data().num_columns())
+            }
+            None => None,
+        }
+    }
+
+    /// Column names of the relation computed for `predicate`, taken from the head of its first
+    /// rule.
+    pub fn column_names(&self, predicate: &str) -> Vec<String> {
+        head_column_names(&self.rules_for(predicate)[0].head)
+    }
+
+    /// Evaluates the program stratum by stratum and registers the relation computed for each IDB
+    /// predicate in `database`, so later rules (and later programs) can query it. Recursive strata
+    /// are evaluated semi-naively until no new tuples are derived; the size of every round's
+    /// deltas is logged and returned.
+    pub fn evaluate(&self, database: &mut Database) -> Result<Vec<Iteration>, String> {
+        self.validate(database)?;
+        // every rule is reduced to its core once, not at each of its evaluations
+        let program = Program::new(self.rules.iter().map(Query::core).collect());
+        let mut iterations = vec![];
+        for (index, stratum) in program.strata().iter().enumerate() {
+            iterations.extend(program.evaluate_stratum(index, stratum, database));
+        }
+        Ok(iterations)
+    }
+
+    fn evaluate_stratum(
+        &self,
+        index: usize,
+        stratum: &[String],
+        database: &mut Database,
+    ) -> Vec<Iteration> {
+        for predicate in stratum {
+            let columns = self.column_names(predicate);
+            database.add_derived_table(Table::new_empty_relation(predicate.clone(), &columns));
+        }
+        let mut iterations = vec![];
+        // the first round evaluates every rule on the full relations
+        let mut deltas = stratum
+            .iter()
+            .map(|predicate| {
+                let delta = self.union_of(predicate, self.rules_for(predicate), database);
+                (predicate.clone(), delta)
+            })
+            .collect::<Vec<_>>();
+        loop {
+            for (predicate, delta) in &deltas {
+                let table = database.get_table(predicate).union(delta);
+                database.set_table(predicate, table);
+            }
+            let iteration = Iteration {
+                stratum: index,
+                round: iterations.len(),
+                deltas: deltas
+                    .iter()
+                    .map(|(predicate, delta)| (predicate.clone(), delta.get_data().num_rows()))
+                    .collect(),
+            };
+            info!("{}", iteration);
+            iterations.push(iteration);
+            if !self.is_recursive(stratum) || deltas.iter().all(|(_, delta)| delta.is_empty()) {
+                return iterations;
+            }
+            // later rounds only derive tuples that use at least one tuple of the last delta
+            let mut with_deltas = database.clone();
+            for (predicate, delta) in &deltas {
+                let mut delta = delta.clone();
+                delta.set_name(&delta_name(predicate));
+                with_deltas.add_table(delta);
+            }
+            deltas = stratum
+                .iter()
+                .map(|predicate| {
+                    let rules = self
+                        .rules_for(predicate)
+                        .into_iter()
+                        .flat_map(|rule| delta_rules(rule, stratum))
+                        .collect::<Vec<_>>();
+                    let derived = self.union_of(predicate, rules.iter().collect(), &with_deltas);
+                    let delta = derived.difference(database.get_table(predicate));
+                    (predicate.clone(), delta)
+                })
+                .collect();
+        }
+    }
+
+    /// Evaluates `rules`, all defining `predicate`, and returns the distinct tuples they derive.
+    fn union_of(&self, predicate: &str, rules: Vec<&Query>, database: &Database) -> Table {
+        let columns = self.column_names(predicate);
+        let mut result = Table::new_empty_relation(predicate.to_string(), &columns);
+        for rule in rules {
+            let table = rule.evaluate_core(database).with_column_names(&columns);
+            result = result.union(&table);
+        }
+        result.distinct()
+    }
+}
+
+/// Column names of the relation defined by a rule head: the head variables, with positional names
+/// for variables repeated in the head.
+pub fn head_column_names(head: &Atom) -> Vec<String> {
+    head.terms
+        .iter()
+        .enumerate()
+        .map(|(index, term)| {
+            let repeated = head.terms.iter().filter(|t| *t == term).count() > 1;
+            match term {
+                Term::Variable(name) if !repeated => name.clone(),
+                _ => format!("c{}", index),
+            }
+        })
+        .collect()
+}
+
+/// The variants of a rule used by semi-naive evaluation: one per body atom over a predicate of
+/// `stratum`, with that atom reading the last delta of its predicate instead of the full relation.
+fn delta_rules(rule: &Query, stratum: &[String]) -> Vec<Query> {
+    let mut rules = vec![];
+    for (index, atom) in rule.body.iter().enumerate() {
+        if stratum.contains(&atom.relation_name) {
+            let mut body = rule.body.clone();
+            body[index].relation_name = delta_name(&atom.relation_name);
+            rules.push(Query {
+                head: rule.head.clone(),
+                body,
+                negated: rule.negated.clone(),
+            });
+        }
+    }
+    rules
+}
+
+fn delta_name(predicate: &str) -> String {
+    format!("{}#delta", predicate)
+}
+
+/// Number of new tuples derived for each predicate of a stratum in one round of evaluation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Iteration {
+    pub stratum: usize,
+    pub round: usize,
+    pub deltas: Vec<(String, usize)>,
+}
+
+impl fmt::Display for Iteration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let deltas = self
+            .deltas
+            .iter()
+            .map(|(predicate, size)| format!("{}: {}", predicate, size))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "stratum {} round {}: {}",
+            self.stratum,
+            self.round,
+            deltas.join(", ")
+        )
+    }
+}
+
+/// Tarjan's algorithm over the IDB dependency graph; components come out dependencies first.
+#[derive(Default)]
+struct StrataBuilder {
+    index: HashMap<String, usize>,
+    low_link: HashMap<String, usize>,
+    stack: Vec<String>,
+    strata: Vec<Vec<String>>,
+}
+
+impl StrataBuilder {
+    fn visit(&mut self, program: &Program, predicate: &str) {
+        let index = self.index.len();
+        self.index.insert(predicate.to_string(), index);
+        self.low_link.insert(predicate.to_string(), index);
+        self.stack.push(predicate.to_string());
+        for dependency in program.dependencies(predicate) {
+            if !self.index.contains_key(&dependency) {
+                self.visit(program, &dependency);
+                let low_link = self.low_link[predicate].min(self.low_link[&dependency]);
+                self.low_link.insert(predicate.to_string(), low_link);
+            } else if self.stack.contains(&dependency) {
+                let low_link = self.low_link[predicate].min(self.index[&dependency]);
+                self.low_link.insert(predicate.to_string(), low_link);
+            }
+        }
+        if self.low_link[predicate] == index {
+            let start = self.stack.iter().position(|p| p == predicate).unwrap();
+            let mut stratum = self.stack.split_off(start);
+            stratum.sort_by_key(|p| program.idb_predicates().iter().position(|q| q == p));
+            self.strata.push(stratum);
+        }
+    }
+}
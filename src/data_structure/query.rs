@@ -1,46 +1,493 @@
+use std::collections::HashMap;
 use std::fmt;
+use std::time::Instant;
 
+use arrow::array::BooleanArray;
+
+use log::info;
+
+use crate::data_structure::cost;
 use crate::data_structure::database::Database;
+use crate::data_structure::explain::{Analysis, ComponentPlan, Fallback, Plan, Strategy};
+use crate::data_structure::homomorphism::{find_homomorphism, identity, is_contained};
 use crate::data_structure::hypergraph::Hypergraph;
-use crate::data_structure::join_tree::JoinTree;
+use crate::data_structure::interrupt;
+use crate::data_structure::join_tree::{JoinForest, JoinTree};
+use crate::data_structure::parallel;
+use crate::data_structure::profile::{self, Phase};
 use crate::data_structure::relational_algebra;
+use crate::data_structure::stream::Stream;
 use crate::data_structure::table::Table;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Query {
     pub head: Atom,
     pub body: Vec<Atom>,
+    /// Atoms negated in the body (`not Atom(...)`), evaluated as anti-joins once the positive
+    /// atoms are joined.
+    pub negated: Vec<Atom>,
 }
 
 impl Query {
+    /// Runs Yannakakis' algorithm along the cheapest join forest of the query on `database`.
     pub fn yannakakis(&self, database: Database) -> Table {
+        match self.cheapest_join_forest(&database) {
+            Some(join_forest) if !join_forest.trees.is_empty() => {
+                self.yannakakis_with_join_forest(database, &join_forest)
+            }
+            _ => Table::new_empty(self.head.relation_name.clone()),
+        }
+    }
+
+    /// Runs Yannakakis' algorithm on every component of the body along its tree of
+    /// `join_forest`, then combines the results by a cartesian product. Components without head
+    /// variables are only checked to have a match.
+    pub fn yannakakis_with_join_forest(
+        &self,
+        database: Database,
+        join_forest: &JoinForest,
+    ) -> Table {
+        if let [join_tree] = join_forest.trees.as_slice() {
+            return self.yannakakis_with_join_tree(database, join_tree);
+        }
         if self.is_boolean() {
             return Table::new_empty(self.head.relation_name.clone());
         }
-        let Some(join_tree) = self.construct_join_tree() else {
+        let mut holds = true;
+        let mut joined: Option<(Atom, Table)> = None;
+        for join_tree in &join_forest.trees {
+            interrupt::check();
+            let body = self
+                .body
+                .iter()
+                .filter(|atom| join_tree.contains(atom))
+                .cloned()
+                .collect();
+            let component = self.component(body);
+            if component.is_boolean() {
+                holds &= component.yannakakis_boolean(&database);
+                continue;
+            }
+            let table = component.yannakakis_with_join_tree(database.clone(), join_tree);
+            joined = Some(match joined {
+                None => (component.head, table),
+                Some((left, left_table)) => {
+                    let product =
+                        relational_algebra::join(&left, &component.head, &left_table, &table);
+                    let left = Atom {
+                        relation_name: left.relation_name.clone(),
+                        terms: Atom::union(&left, &component.head),
+                    };
+                    (left, product)
+                }
+            });
+        }
+        let Some((_, table)) = joined else {
+            return Table::new_empty(self.head.relation_name.clone());
+        };
+        let table = table.project(&self.head.terms);
+        if holds {
+            table
+        } else {
+            table.filter(&BooleanArray::from(vec![
+                false;
+                table.get_data().num_rows()
+            ]))
+        }
+    }
+
+    /// Runs Yannakakis' algorithm along `join_tree`, which must be a join tree of the query.
+    pub fn yannakakis_with_join_tree(&self, database: Database, join_tree: &JoinTree) -> Table {
+        if self.is_boolean() {
             return Table::new_empty(self.head.relation_name.clone());
+        }
+        self.yannakakis_stream(database, join_tree)
+            .into_table(self.head.relation_name.clone())
+    }
+
+    /// Runs Yannakakis' algorithm along `join_tree` and streams the answers: the subtrees of the
+    /// root are joined first, then the root is scanned batch by batch and probed against their
+    /// results, so the answers are produced as they are pulled instead of being materialized.
+    pub fn yannakakis_stream(&self, database: Database, join_tree: &JoinTree) -> Stream {
+        let o_database = self.construct_consistent_db(join_tree, &database);
+        let root = join_tree.get_root();
+        let children = join_tree.get_children(root);
+        let results = parallel::map(&children, |child| {
+            self.join_subtree(join_tree, child, &o_database)
+        });
+        profile::at(Phase::Join, root, || {
+            let mut atom = Atom {
+                relation_name: root.relation_name.clone(),
+                terms: root.variables(),
+            };
+            let mut stream =
+                Stream::scan(o_database.get_table(&root.relation_name)).project(&atom.terms);
+            for (child_atom, o_child) in results {
+                stream = stream.join(&atom, &child_atom, &o_child);
+                atom.terms = Atom::union(&atom, &child_atom)
+                    .into_iter()
+                    .filter(|term| root.terms.contains(term) || self.head.terms.contains(term))
+                    .collect();
+                stream = stream.project(&atom.terms);
+            }
+            stream.project(&self.head.terms).distinct()
+        })
+    }
+
+    /// The answers of the query on `catalog` as a stream (see [`Query::evaluate`]). Bodies made of
+    /// a single acyclic component stream their answers out of the join phase of Yannakakis'
+    /// algorithm; other queries are evaluated first and their result is then streamed.
+    pub fn stream(&self, catalog: &Database) -> Stream {
+        let core = self.core();
+        let aliased = core.with_distinct_relation_names();
+        let streamable = core.negated.is_empty()
+            && !core.is_boolean()
+            && core.head.terms.len() == core.head.variables().len();
+        if streamable {
+            let database = catalog.bind(&core, &aliased);
+            if let Some(join_forest) = aliased.cheapest_join_forest(&database) {
+                if let [join_tree] = join_forest.trees.as_slice() {
+                    return aliased.yannakakis_stream(database, join_tree);
+                }
+            }
+        }
+        Stream::scan(&core.evaluate_core(catalog))
+    }
+
+    /// The join pass below `s`, returning the columns it keeps, as an atom, with their table.
+    fn join_subtree(&self, join_tree: &JoinTree, s: &Atom, o_database: &Database) -> (Atom, Table) {
+        let children = join_tree.get_children(s);
+        let results = parallel::map(&children, |child| {
+            self.join_subtree(join_tree, child, o_database)
+        });
+        profile::at(Phase::Join, s, || {
+            let mut atom = Atom {
+                relation_name: s.relation_name.clone(),
+                terms: s.variables(),
+            };
+            // the joins are pipelined, and only their deduplicated result is kept
+            let mut stream =
+                Stream::scan(o_database.get_table(&s.relation_name)).project(&atom.terms);
+            for (child_atom, o_child) in results {
+                stream = stream.join(&atom, &child_atom, &o_child);
+                atom.terms = Atom::union(&atom, &child_atom)
+                    .into_iter()
+                    .filter(|term| s.terms.contains(term) || self.head.terms.contains(term))
+                    .collect();
+                stream = stream.project(&atom.terms).distinct();
+            }
+            let o_s = stream.into_table(s.relation_name.clone());
+            (atom, o_s)
+        })
+    }
+
+    /// Evaluates the query against the relations of `catalog` and returns one column per head
+    /// term. Redundant atoms are removed first (see [`Query::minimize`]). Acyclic bodies go
+    /// through Yannakakis along the cheapest join tree of each of their connected components;
+    /// cyclic bodies and boolean heads are joined atom by atom.
+    pub fn evaluate(&self, catalog: &Database) -> Table {
+        self.core().evaluate_core(catalog)
+    }
+
+    /// The query with its positive body reduced to its core (see [`Query::minimize`]), keeping in
+    /// place the head variables and the variables of the negated atoms.
+    pub(crate) fn core(&self) -> Query {
+        let body = self.positive().minimize().body;
+        Query {
+            head: self.head.clone(),
+            body,
+            negated: self.negated.clone(),
+        }
+    }
+
+    /// Evaluates the query as it is written, without looking for redundant atoms, as
+    /// [`Query::evaluate`] does once it has its core.
+    pub(crate) fn evaluate_core(&self, catalog: &Database) -> Table {
+        if self.head.terms.len() != self.head.variables().len() {
+            return self.evaluate_with_constant_head(catalog);
+        }
+        if !self.negated.is_empty() {
+            return self.evaluate_with_negation(catalog);
+        }
+        let aliased = self.with_distinct_relation_names();
+        let database = catalog.bind(self, &aliased);
+        match aliased.cheapest_join_forest(&database) {
+            Some(join_forest) if !self.is_boolean() && !join_forest.trees.is_empty() => {
+                aliased.yannakakis_with_join_forest(database, &join_forest)
+            }
+            _ => aliased.join_all(&database),
+        }
+    }
+
+    /// The plan [`Query::evaluate`] follows on `catalog`, without evaluating anything.
+    pub fn explain(&self, catalog: &Database) -> Plan {
+        let core = self.core();
+        let removed = self
+            .body
+            .iter()
+            .filter(|atom| !core.body.contains(atom))
+            .cloned()
+            .collect();
+        // the positive part is evaluated on the head variables and the variables of the negated
+        // atoms, as in `evaluate_with_constant_head` and `evaluate_with_negation`
+        let positive = core.positive();
+        let aliased = positive.with_distinct_relation_names();
+        let database = catalog.bind(&positive, &aliased);
+        let strategy = match aliased.cheapest_join_forest(&database) {
+            Some(join_forest) if !aliased.is_boolean() && !join_forest.trees.is_empty() => {
+                let components = aliased
+                    .components()
+                    .into_iter()
+                    .zip(join_forest.trees)
+                    .map(|(component, join_tree)| {
+                        // boolean components are checked along their first join tree
+                        let join_tree = if component.is_boolean() {
+                            component.construct_join_tree().unwrap()
+                        } else {
+                            join_tree
+                        };
+                        ComponentPlan::new(component, join_tree, &database)
+                    })
+                    .collect();
+                Strategy::Yannakakis(components)
+            }
+            join_forest => {
+                let reason = if join_forest.is_none() {
+                    Fallback::Cyclic
+                } else if aliased.body.is_empty() {
+                    Fallback::EmptyBody
+                } else {
+                    Fallback::Boolean
+                };
+                Strategy::LeftDeepJoin {
+                    reason,
+                    order: aliased.body.clone(),
+                }
+            }
         };
-        let mut o_database = self.construct_consistent_db(&join_tree, &database);
-        let mut nodes = join_tree.get_nodes();
-        while !nodes.is_empty() {
-            let s = &join_tree.find_node_with_no_child_in_nodes(&nodes).unwrap();
-            if !join_tree.is_leaf(s) {
-                let union = Atom::union(s, &self.head);
-                for child in join_tree.get_children(s) {
-                    let join = relational_algebra::join(
-                        s,
-                        &child,
-                        o_database.get_table(&s.relation_name),
-                        o_database.get_table(&child.relation_name),
-                    );
-                    let o_s = join.project(&union);
-                    o_database.set_table(&s.relation_name, o_s);
+        Plan {
+            query: aliased,
+            removed,
+            strategy,
+            anti_joins: core.negated.clone(),
+            head: core.head.terms.clone(),
+        }
+    }
+
+    /// Evaluates the query on `catalog` and returns its plan (see [`Query::explain`]) along with
+    /// the rows, time and memory of the operators run for every node in every phase.
+    pub fn explain_analyze(&self, catalog: &Database) -> Analysis {
+        let plan = self.explain(catalog);
+        let start = Instant::now();
+        let (answers, profile) = profile::profile(|| self.evaluate(catalog));
+        Analysis {
+            plan,
+            profile,
+            answers,
+            time: start.elapsed(),
+        }
+    }
+
+    /// Renames the repeated relations of the body, so that each atom gets its own copy.
+    fn with_distinct_relation_names(&self) -> Query {
+        let mut occurrences: HashMap<&str, usize> = HashMap::new();
+        let body = self
+            .body
+            .iter()
+            .map(|atom| {
+                let count = occurrences.entry(&atom.relation_name).or_insert(0);
+                *count += 1;
+                let mut alias = atom.clone();
+                if *count > 1 {
+                    alias.relation_name = format!("{}#{}", atom.relation_name, count);
+                }
+                alias
+            })
+            .collect();
+        Query {
+            head: self.head.clone(),
+            body,
+            negated: self.negated.clone(),
+        }
+    }
+
+    /// Evaluates the distinct head variables, then lays out the constants and repeats of the head.
+    fn evaluate_with_constant_head(&self, catalog: &Database) -> Table {
+        let variables = Query {
+            head: Atom {
+                relation_name: self.head.relation_name.clone(),
+                terms: self.head.variables(),
+            },
+            body: self.body.clone(),
+            negated: self.negated.clone(),
+        };
+        variables
+            .evaluate_core(catalog)
+            .project_with_constants(&self.head.terms)
+    }
+
+    /// The positive atoms, with a head made of the head variables and every variable the negated
+    /// atoms need.
+    fn positive(&self) -> Query {
+        let mut terms = self.head.variables();
+        for term in self.negated.iter().flat_map(|atom| atom.variables()) {
+            if !term.is_anonymous() && !terms.contains(&term) {
+                terms.push(term);
+            }
+        }
+        Query {
+            head: Atom {
+                relation_name: self.head.relation_name.clone(),
+                terms,
+            },
+            body: self.body.clone(),
+            negated: vec![],
+        }
+    }
+
+    /// Joins the positive atoms, keeping every variable the negated atoms need, then removes the
+    /// tuples matched by a negated atom.
+    fn evaluate_with_negation(&self, catalog: &Database) -> Table {
+        let positive = self.positive();
+        let mut table = positive.evaluate_core(catalog);
+        for atom in &self.negated {
+            let negated = Query {
+                head: Atom {
+                    relation_name: atom.relation_name.clone(),
+                    terms: atom.variables(),
+                },
+                body: vec![atom.clone()],
+                negated: vec![],
+            };
+            table = relational_algebra::anti_join(
+                &positive.head,
+                &negated.head,
+                &table,
+                &profile::without(|| negated.evaluate_core(catalog)),
+            );
+        }
+        table.project(&self.head.terms)
+    }
+
+    /// Checks that the query is safe: every variable of the head and every named variable of the
+    /// negated atoms occurs in a positive atom of the body.
+    pub fn check_safety(&self) -> Result<(), String> {
+        let bound = self
+            .body
+            .iter()
+            .flat_map(|atom| atom.terms.iter())
+            .collect::<Vec<_>>();
+        for term in &self.head.terms {
+            if let Term::Variable(name) = term {
+                if !bound.contains(&term) {
+                    return Err(format!(
+                        "variable {} in the head of {} does not occur in its body",
+                        name, self.head.relation_name
+                    ));
                 }
             }
-            nodes.remove(s);
         }
-        let big_o_r = o_database.get_table(&join_tree.get_root().relation_name);
-        big_o_r.project(&self.head.terms)
+        for atom in &self.negated {
+            for term in &atom.terms {
+                if let Term::Variable(name) = term {
+                    if !term.is_anonymous() && !bound.contains(&term) {
+                        return Err(format!(
+                            "variable {} of not {} does not occur in a positive atom",
+                            name, atom.relation_name
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn join_all(&self, database: &Database) -> Table {
+        let mut joined: Option<(Atom, Table)> = None;
+        for atom in &self.body {
+            interrupt::check();
+            joined = Some(profile::at(Phase::Join, atom, || {
+                let variables = atom.variables();
+                let table = self.compute_atom(atom, database).project(&variables);
+                let atom = Atom {
+                    relation_name: atom.relation_name.clone(),
+                    terms: variables,
+                };
+                match joined {
+                    None => (atom, table),
+                    Some((left, left_table)) => {
+                        let join = relational_algebra::join(&left, &atom, &left_table, &table);
+                        let left = Atom {
+                            relation_name: left.relation_name.clone(),
+                            terms: Atom::union(&left, &atom),
+                        };
+                        (left, join)
+                    }
+                }
+            }));
+        }
+        match joined {
+            Some((_, table)) => table.project(&self.head.terms),
+            None => Table::new_empty(self.head.relation_name.clone()),
+        }
+    }
+
+    /// Computes the core of the query: body atoms are dropped as long as the whole body still maps
+    /// homomorphically onto the remaining atoms with the head variables left in place. The result
+    /// is equivalent to the query and has the fewest atoms possible, which also shrinks its
+    /// hypergraph and can make a cyclic query acyclic. Queries with negated atoms are returned
+    /// unchanged, since dropping positive atoms may change what the negation removes.
+    pub fn minimize(&self) -> Query {
+        if !self.negated.is_empty() {
+            return self.clone();
+        }
+        let mut body = self.body.clone();
+        let mut index = 0;
+        while index < body.len() {
+            let mut candidate = body.clone();
+            candidate.remove(index);
+            if find_homomorphism(&body, &candidate, identity(&self.head)).is_some() {
+                body = candidate;
+            } else {
+                index += 1;
+            }
+        }
+        Query {
+            head: self.head.clone(),
+            body,
+            negated: vec![],
+        }
+    }
+
+    /// Whether every answer of this query is an answer of `other`, on every database.
+    pub fn is_contained_in(&self, other: &Query) -> bool {
+        is_contained(self, other)
+    }
+
+    /// Whether the two queries have the same answers on every database.
+    pub fn is_equivalent_to(&self, other: &Query) -> bool {
+        is_contained(self, other) && is_contained(other, self)
+    }
+
+    /// Whether the body has at least one match in `catalog`, whatever the head.
+    pub(crate) fn holds(&self, catalog: &Database) -> bool {
+        let aliased = self.with_distinct_relation_names();
+        let database = catalog.bind(self, &aliased);
+        let boolean = Query {
+            head: Atom {
+                relation_name: aliased.head.relation_name.clone(),
+                terms: vec![],
+            },
+            body: aliased.body.clone(),
+            negated: vec![],
+        };
+        match boolean.construct_join_forest() {
+            Some(join_forest) if !join_forest.trees.is_empty() => boolean
+                .components()
+                .iter()
+                .all(|component| component.yannakakis_boolean(&database)),
+            _ => !boolean.join_all(&database).is_empty(),
+        }
     }
 
     pub fn is_boolean(&self) -> bool {
@@ -66,30 +513,38 @@ impl Query {
         db: &Database,
     ) -> Database {
         let mut big_q: Database = db.clone();
-        let mut nodes = join_tree.get_nodes();
-        while !nodes.is_empty() {
-            let s = &join_tree.find_node_with_no_child_in_nodes(&nodes).unwrap();
-            let q_s = self.compute_atom(s, &big_q);
-            if join_tree.get_children(s).is_empty() {
-                big_q.set_table(&s.relation_name, q_s);
-            } else {
-                let mut big_q_s = q_s.clone();
-                for child in join_tree.get_children(s) {
-                    let semi_join = relational_algebra::semi_join(
-                        s,
-                        &child,
-                        &q_s,
-                        big_q.get_table(&child.relation_name),
-                    );
-                    big_q_s = semi_join.intersection(&big_q_s);
-                }
-
-                big_q.set_table(&s.relation_name, big_q_s);
-            }
-            nodes.remove(s);
+        for (name, table) in self.reduce_subtree_up(join_tree, join_tree.get_root(), db) {
+            big_q.set_table(&name, table);
         }
         big_q
     }
+
+    /// The bottom-up semi-join pass below `s`, with the subtrees of its children reduced in
+    /// parallel. Returns the reduced table of every node of the subtree, `s` last.
+    fn reduce_subtree_up(
+        &self,
+        join_tree: &JoinTree,
+        s: &Atom,
+        db: &Database,
+    ) -> Vec<(String, Table)> {
+        let children = join_tree.get_children(s);
+        let subtrees = parallel::map(&children, |child| {
+            self.reduce_subtree_up(join_tree, child, db)
+        });
+        let q_s = profile::at(Phase::PostOrder, s, || {
+            let mut q_s = self.compute_atom(s, db);
+            for (child, subtree) in children.iter().zip(&subtrees) {
+                interrupt::check();
+                let (_, q_child) = subtree.last().unwrap();
+                let indexes = db.indexes(&child.relation_name);
+                q_s = relational_algebra::semi_join(s, child, &q_s, q_child, indexes);
+            }
+            q_s
+        });
+        let mut tables = subtrees.concat();
+        tables.push((s.relation_name.clone(), q_s));
+        tables
+    }
     pub fn remove_dangling_tuple_pre_order(&self, join_tree: &JoinTree, db: &Database) -> Database {
         let mut a_database = db.clone();
         let root = join_tree.get_root();
@@ -97,23 +552,37 @@ impl Query {
             &root.relation_name,
             db.get_table(&root.relation_name).clone(),
         );
-        let mut nodes = join_tree.get_nodes();
-        while !nodes.is_empty() {
-            let s = &join_tree.find_node_with_no_parent_in_nodes(&nodes).unwrap();
-            for child in join_tree.get_children(s) {
-                let a_child = relational_algebra::semi_join(
-                    &child,
-                    s,
-                    db.get_table(&child.relation_name),
-                    a_database.get_table(&s.relation_name),
-                );
-                a_database.set_table(&child.relation_name, a_child);
-            }
-            nodes.remove(s);
+        let a_root = db.get_table(&root.relation_name);
+        for (name, table) in self.reduce_subtree_down(join_tree, root, a_root, db) {
+            a_database.set_table(&name, table);
         }
         a_database
     }
 
+    /// The top-down semi-join pass below `s`, whose reduced table is `a_s`, with the subtrees of
+    /// its children reduced in parallel. Returns the reduced table of every node below `s`.
+    fn reduce_subtree_down(
+        &self,
+        join_tree: &JoinTree,
+        s: &Atom,
+        a_s: &Table,
+        db: &Database,
+    ) -> Vec<(String, Table)> {
+        let children = join_tree.get_children(s);
+        let subtrees = parallel::map(&children, |child| {
+            interrupt::check();
+            let a_child = profile::at(Phase::PreOrder, child, || {
+                let a_child = db.get_table(&child.relation_name);
+                // `a_s` was reduced by the post-order pass, so no index covers its columns
+                relational_algebra::semi_join(child, s, a_child, a_s, &[])
+            });
+            let mut tables = self.reduce_subtree_down(join_tree, child, &a_child, db);
+            tables.push((child.relation_name.clone(), a_child));
+            tables
+        });
+        subtrees.concat()
+    }
+
     pub fn is_acyclic(&self) -> bool {
         let hypergraph = Hypergraph::new(self);
         hypergraph.is_acyclic()
@@ -121,20 +590,28 @@ impl Query {
 
     fn compute_atom(&self, atom: &Atom, database: &Database) -> Table {
         let node_table = database.get_table(&atom.relation_name);
-        relational_algebra::select(atom, node_table)
+        relational_algebra::select_indexed(atom, node_table, database.indexes(&atom.relation_name))
     }
 
+    /// Builds a join tree by removing ears in body order, each hanging from its first witness.
+    /// Returns `None` when the query is cyclic, or when its body is empty or disconnected, in which
+    /// case [`Query::construct_join_forest`] gives one tree per component.
     pub fn construct_join_tree(&self) -> Option<JoinTree> {
+        if self.components().len() != 1 {
+            return None;
+        }
         let mut join_tree = JoinTree::default();
         let mut hypergraph = Hypergraph::new(self);
 
         while let Some((ear, witness)) = hypergraph.find_ear() {
             if ear == witness {
-                hypergraph.hyperedges.remove(&ear);
+                // the last atom of the component, which is the root unless it is alone
+                join_tree.add_node(ear.clone());
+                hypergraph.remove(&ear);
                 continue;
             }
             join_tree.add_edge(witness.clone(), ear.clone());
-            hypergraph.hyperedges.remove(&ear);
+            hypergraph.remove(&ear);
         }
         if !hypergraph.is_empty() {
             // hypergraph is not acyclic
@@ -143,17 +620,162 @@ impl Query {
         Some(join_tree)
     }
 
+    /// The join trees of the query, each with every possible root, in a deterministic order: ears
+    /// are removed in body order as by [`Query::construct_join_tree`], but every witness of an ear
+    /// is tried as its parent. At most [`MAX_JOIN_TREES`] trees are built before choosing roots.
+    pub fn join_trees(&self) -> Vec<JoinTree> {
+        let mut trees = vec![];
+        if self.components().len() != 1 {
+            return trees;
+        }
+        enumerate_join_trees(Hypergraph::new(self), JoinTree::default(), &mut trees);
+        let mut rooted: Vec<JoinTree> = vec![];
+        for tree in &trees {
+            let mut nodes = tree.get_nodes().iter().collect::<Vec<_>>();
+            nodes.sort_by_key(|node| self.body.iter().position(|atom| atom == *node));
+            for node in nodes {
+                let candidate = tree.with_root(node).unwrap();
+                if !rooted.contains(&candidate) {
+                    rooted.push(candidate);
+                }
+            }
+        }
+        rooted
+    }
+
+    /// The join tree with the lowest estimated cost on `database` (see
+    /// [`cost::join_tree_cost`]), the first enumerated one on ties. Returns `None` when the query
+    /// has no join tree.
+    pub fn cheapest_join_tree(&self, database: &Database) -> Option<JoinTree> {
+        let mut cheapest: Option<(JoinTree, f64)> = None;
+        for join_tree in self.join_trees() {
+            let cost = cost::join_tree_cost(self, &join_tree, database);
+            if cheapest.as_ref().is_none_or(|(_, best)| cost < *best) {
+                cheapest = Some((join_tree, cost));
+            }
+        }
+        let (join_tree, cost) = cheapest?;
+        info!(
+            "join tree for {} rooted at {} (estimated cost {})",
+            self,
+            join_tree.get_root(),
+            cost
+        );
+        Some(join_tree)
+    }
+
+    /// One join tree per connected component of the body, in body order. Returns `None` when a
+    /// component is cyclic.
+    pub fn construct_join_forest(&self) -> Option<JoinForest> {
+        let trees = self
+            .components()
+            .iter()
+            .map(|component| component.construct_join_tree())
+            .collect::<Option<Vec<_>>>()?;
+        Some(JoinForest { trees })
+    }
+
+    /// The forest made of the cheapest join tree of every connected component of the body.
+    pub fn cheapest_join_forest(&self, database: &Database) -> Option<JoinForest> {
+        let trees = self
+            .components()
+            .iter()
+            .map(|component| component.cheapest_join_tree(database))
+            .collect::<Option<Vec<_>>>()?;
+        Some(JoinForest { trees })
+    }
+
+    /// Splits the body into its connected components: maximal sets of atoms linked by shared
+    /// variables. Each component becomes a query of its own, in body order, whose head keeps the
+    /// head variables occurring in it.
+    pub fn components(&self) -> Vec<Query> {
+        let mut components: Vec<Vec<&Atom>> = vec![];
+        for atom in &self.body {
+            if components.iter().flatten().any(|other| *other == atom) {
+                continue;
+            }
+            let (linked, mut rest): (Vec<_>, Vec<_>) =
+                components.into_iter().partition(|component| {
+                    component
+                        .iter()
+                        .any(|other| !atom.shared_variables(other).is_empty())
+                });
+            let mut component = linked.concat();
+            component.push(atom);
+            rest.push(component);
+            components = rest;
+        }
+        let position = |atom: &Atom| self.body.iter().position(|other| other == atom);
+        for component in components.iter_mut() {
+            component.sort_by_key(|atom| position(atom));
+        }
+        components.sort_by_key(|component| position(component[0]));
+        components
+            .into_iter()
+            .map(|atoms| self.component(atoms.into_iter().cloned().collect()))
+            .collect()
+    }
+
+    /// The query restricted to `body`, with the head variables occurring in it.
+    fn component(&self, body: Vec<Atom>) -> Query {
+        let terms = self
+            .head
+            .variables()
+            .into_iter()
+            .filter(|variable| body.iter().any(|atom| atom.terms.contains(variable)))
+            .collect();
+        Query {
+            head: Atom {
+                relation_name: self.head.relation_name.clone(),
+                terms,
+            },
+            body,
+            negated: vec![],
+        }
+    }
+
+    /// The join tree of [`Query::construct_join_tree`], rooted at `root` instead. Returns `None`
+    /// when the query is cyclic or `root` is not a node of its join tree.
+    pub fn construct_join_tree_with_root(&self, root: &Atom) -> Option<JoinTree> {
+        self.construct_join_tree()?.with_root(root)
+    }
+
     fn construct_consistent_db(&self, join_tree: &JoinTree, database: &Database) -> Database {
         let big_q = self.remove_dangling_tuple_post_order(join_tree, database);
         self.remove_dangling_tuple_pre_order(join_tree, &big_q)
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-struct Variable(String);
+/// Bound on the number of join trees [`Query::join_trees`] builds, since a query can have
+/// exponentially many.
+pub const MAX_JOIN_TREES: usize = 32;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-struct Constant(String);
+/// Removes the first ear of `hypergraph` and continues with each of its witnesses as its parent,
+/// collecting every complete join tree.
+fn enumerate_join_trees(hypergraph: Hypergraph, join_tree: JoinTree, trees: &mut Vec<JoinTree>) {
+    if trees.len() >= MAX_JOIN_TREES {
+        return;
+    }
+    let Some((ear, witness)) = hypergraph.find_ear() else {
+        if hypergraph.is_empty() {
+            trees.push(join_tree);
+        }
+        return;
+    };
+    let mut remaining = hypergraph.clone();
+    remaining.remove(&ear);
+    if ear == witness {
+        let mut extended = join_tree;
+        extended.add_node(ear);
+        enumerate_join_trees(remaining, extended, trees);
+        return;
+    }
+    for witness in hypergraph.find_witnesses(&ear) {
+        let mut extended = join_tree.clone();
+        extended.add_edge(witness, ear.clone());
+        enumerate_join_trees(remaining.clone(), extended, trees);
+    }
+}
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Atom {
@@ -169,6 +791,27 @@ impl Atom {
         }
     }
 
+    /// The distinct variables of the atom, in order of first occurrence.
+    pub fn variables(&self) -> Vec<Term> {
+        let mut variables = vec![];
+        for term in &self.terms {
+            if let Term::Variable(_) = term {
+                if !variables.contains(term) {
+                    variables.push(term.clone());
+                }
+            }
+        }
+        variables
+    }
+
+    /// The named variables occurring in both atoms, in the order of this atom.
+    pub fn shared_variables(&self, other: &Atom) -> Vec<Term> {
+        self.variables()
+            .into_iter()
+            .filter(|term| !term.is_anonymous() && other.terms.contains(term))
+            .collect()
+    }
+
     pub fn union(left: &Atom, right: &Atom) -> Vec<Term> {
         let mut result = left.terms.clone();
         for term in right.terms.clone() {
@@ -186,11 +829,85 @@ pub enum Term {
     Constant(String),
 }
 
+impl Term {
+    /// Whether the term is a variable written `_` in the query, which never joins with anything.
+    pub fn is_anonymous(&self) -> bool {
+        matches!(self, Term::Variable(name) if name.starts_with('_'))
+    }
+}
+
 impl fmt::Display for Term {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            Term::Variable(_) if self.is_anonymous() => write!(f, "_"),
             Term::Variable(var) => write!(f, "{}", var),
-            Term::Constant(name) => write!(f, "{}", name),
+            Term::Constant(name) => write!(f, "{}", quote(name, '\'')),
         }
     }
 }
+
+/// Writes `value` between `quote`s the way the parser reads it back: the quote is doubled and
+/// backslashes and control characters are escaped.
+fn quote(value: &str, quote: char) -> String {
+    let mut quoted = String::from(quote);
+    for c in value.chars() {
+        match c {
+            c if c == quote => {
+                quoted.push(quote);
+                quoted.push(quote);
+            }
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push(quote);
+    quoted
+}
+
+/// Relation names are written as is when the parser reads them back unchanged, that is when they
+/// are lowercase identifiers, and between double quotes otherwise.
+fn relation_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let is_identifier = chars.next().is_some_and(|c| c.is_alphanumeric())
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && name.to_lowercase() == name;
+    if is_identifier {
+        name.to_string()
+    } else {
+        quote(name, '"')
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let terms = self
+            .terms
+            .iter()
+            .map(|term| term.to_string())
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "{}({})",
+            relation_name(&self.relation_name),
+            terms.join(",")
+        )
+    }
+}
+
+/// Writes the query in the syntax of the parser, negated atoms last, so that `parse_query` reads
+/// back the same query.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let literals = self
+            .body
+            .iter()
+            .map(|atom| atom.to_string())
+            .chain(self.negated.iter().map(|atom| format!("not {}", atom)))
+            .collect::<Vec<_>>();
+        write!(f, "{}:-{}.", self.head, literals.join(","))
+    }
+}
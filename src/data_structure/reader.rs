@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use arrow;
 use arrow::array::RecordBatch;
@@ -9,19 +9,29 @@ use arrow_select::concat::concat_batches;
 
 use crate::data_structure::database::Database;
 use crate::data_structure::query::Query;
+use crate::data_structure::stream::{Stream, BATCH_SIZE};
 use crate::data_structure::table::Table;
 
 fn load(path: &str, schema: &SchemaRef) -> RecordBatch {
+    let batches = scan_file(path, schema).collect::<Vec<_>>();
+    concat_batches(schema, &batches).unwrap()
+}
+
+/// Reads a CSV file of the data directory lazily, [`BATCH_SIZE`] rows at a time.
+fn scan_file(path: &str, schema: &SchemaRef) -> Stream {
     let file = File::open(format!("data/{}", path)).unwrap();
     let csv_reader = arrow_csv::ReaderBuilder::new(schema.clone())
         .with_header(true)
+        .with_batch_size(BATCH_SIZE)
         .build(file)
         .unwrap();
-    let batches = csv_reader
-        .into_iter()
-        .map(|x| x.unwrap())
-        .collect::<Vec<_>>();
-    concat_batches(schema, &batches).unwrap()
+    Stream::new(schema.clone(), csv_reader.map(|x| x.unwrap()))
+}
+
+/// Streams a relation of the database on disk straight from its file, without loading it.
+pub fn scan(relation_name: &str) -> Option<Stream> {
+    let schema = schema(relation_name)?;
+    Some(scan_file(&format!("{}.csv", relation_name), &schema))
 }
 
 pub fn beers() -> SchemaRef {
@@ -78,38 +88,44 @@ pub fn styles() -> SchemaRef {
     ]))
 }
 
+/// Schema of a relation of the database on disk.
+pub fn schema(relation_name: &str) -> Option<SchemaRef> {
+    match relation_name {
+        "beers" => Some(beers()),
+        "breweries" => Some(breweries()),
+        "categories" => Some(categories()),
+        "locations" => Some(locations()),
+        "styles" => Some(styles()),
+        _ => None,
+    }
+}
+
 pub fn get_database() -> Database {
-    let beers = load("beers.csv", &beers());
-    let breweries = load("breweries.csv", &breweries());
-    let categories = load("categories.csv", &categories());
-    let locations = load("locations.csv", &locations());
-    let styles = load("styles.csv", &styles());
-    Database {
-        beers: Table {
-            name: "beers".to_string(),
-            data: beers,
-        },
-        breweries: Table {
-            name: "breweries".to_string(),
-            data: breweries,
-        },
-        categories: Table {
-            name: "categories".to_string(),
-            data: categories,
-        },
-        locations: Table {
-            name: "locations".to_string(),
-            data: locations,
-        },
-        styles: Table {
-            name: "styles".to_string(),
-            data: styles,
-        },
+    let mut database = Database::default();
+    for (name, schema) in [
+        ("beers", beers()),
+        ("breweries", breweries()),
+        ("categories", categories()),
+        ("locations", locations()),
+        ("styles", styles()),
+    ] {
+        database.add_table(Table {
+            name: name.to_string(),
+            data: load(&format!("{}.csv", name), &schema),
+        });
     }
+    database.analyze();
+    database
 }
 
+static DATABASE: OnceLock<Arc<Database>> = OnceLock::new();
+
+/// The database on disk, read the first time it is needed and shared by every later caller.
+pub fn shared_database() -> Arc<Database> {
+    DATABASE.get_or_init(|| Arc::new(get_database())).clone()
+}
+
+/// A view of the shared database with the relations of `query` renamed after its terms.
 pub fn get_database_with_query(query: &Query) -> Database {
-    let mut database = get_database();
-    database.rename(query);
-    database
+    shared_database().view(query)
 }
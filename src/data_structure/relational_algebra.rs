@@ -1,26 +1,427 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use arrow::array::{ArrayRef, BooleanArray, RecordBatch, StringArray};
-use arrow_schema::Schema;
+use arrow::array::{
+    ArrayRef, BooleanArray, RecordBatch, RecordBatchOptions, StringArray, UInt32Array,
+};
+use arrow::compute::take;
+use arrow_row::{RowConverter, Rows, SortField};
+use arrow_schema::{DataType, Schema, SchemaRef};
 use arrow_select::filter::filter_record_batch;
+use log::info;
 
+use crate::data_structure::index::Index;
+use crate::data_structure::interrupt::{self, CHECK_ROWS};
+use crate::data_structure::memory::Reservation;
+use crate::data_structure::parallel;
+use crate::data_structure::profile::{self, Operator};
 use crate::data_structure::query::Atom;
 use crate::data_structure::query::Term::{Constant, Variable};
+use crate::data_structure::spill::{self, SpillFile, SpillWriter};
+use crate::data_structure::stream::Stream;
 use crate::data_structure::table::Table;
 
-pub fn semi_join(query: &Atom, query_2: &Atom, table: &Table, table_2: &Table) -> Table {
-    let join_table = join(query, query_2, table, table_2);
-    join_table.project(&query.terms)
+/// Keeps the rows of `table` matching `query` that join with a row of `table_2` matching
+/// `query_2`, projected on the terms of `query`, without duplicates. When the atoms share a single
+/// variable and `table_2` holds the column an index of `indexes` was built on, the rows of `table`
+/// are looked up in that index instead of a hash set built for the occasion.
+pub fn semi_join(
+    query: &Atom,
+    query_2: &Atom,
+    table: &Table,
+    table_2: &Table,
+    indexes: &[Arc<Index>],
+) -> Table {
+    profile::measure(Operator::SemiJoin, table.data.num_rows(), || {
+        semi_join_unmeasured(query, query_2, table, table_2, indexes)
+    })
 }
 
+fn semi_join_unmeasured(
+    query: &Atom,
+    query_2: &Atom,
+    table: &Table,
+    table_2: &Table,
+    indexes: &[Arc<Index>],
+) -> Table {
+    let table = select(query, table);
+    let table_2 = select(query_2, table_2);
+    let keys = join_keys(query, query_2);
+    let index = match keys[..] {
+        [(_, right)] => indexes
+            .iter()
+            .find(|index| index.column() == right && index.covers(&table_2)),
+        _ => None,
+    };
+    let matched = if keys.is_empty() {
+        BooleanArray::from(vec![!table_2.is_empty(); table.data.num_rows()])
+    } else if let Some(index) = index {
+        index.matches(table.data.column(keys[0].0))
+    } else {
+        let (left_keys, right_keys): (Vec<_>, Vec<_>) = keys.into_iter().unzip();
+        let converter = key_converter(left_keys.len());
+        let existing = converter
+            .convert_columns(table_2.projection(&right_keys).data.columns())
+            .unwrap();
+        let existing = existing.iter().collect::<HashSet<_>>();
+        let rows = converter
+            .convert_columns(table.projection(&left_keys).data.columns())
+            .unwrap();
+        let parts = parallel::map(&parallel::partitions(rows.num_rows()), |range| {
+            range
+                .clone()
+                .map(|row| {
+                    if row % CHECK_ROWS == 0 {
+                        interrupt::check();
+                    }
+                    Some(existing.contains(&rows.row(row)))
+                })
+                .collect::<Vec<_>>()
+        });
+        parts.concat().into_iter().collect()
+    };
+    table.filter(&matched).project(&query.terms).distinct()
+}
+
+/// Keeps the rows of `table` that have no matching row in `table_2` on the variables the two atoms
+/// share. When they share no variable, every row is kept only if `table_2` is empty.
+pub fn anti_join(query: &Atom, query_2: &Atom, table: &Table, table_2: &Table) -> Table {
+    let shared = query_2
+        .variables()
+        .into_iter()
+        .filter(|term| query.terms.contains(term))
+        .collect::<Vec<_>>();
+    if shared.is_empty() {
+        return if table_2.is_empty() {
+            table.clone()
+        } else {
+            table.filter(&BooleanArray::from(vec![false; table.data.num_rows()]))
+        };
+    }
+    let matched = table
+        .project(&shared)
+        .contains_rows(&table_2.project(&shared));
+    table.filter(&arrow::compute::not(&matched).unwrap())
+}
+
+/// Joins the rows of the two tables that agree on the variables shared by `left` and `right`,
+/// through a hash table built on `right_table`, and keeps one column per term of the union of the
+/// two atoms. Constants and repeated variables are checked on the matching pairs.
 pub fn join(left: &Atom, right: &Atom, left_table: &Table, right_table: &Table) -> Table {
-    let cartesian_product = cartesian_product(left_table, right_table);
-    let mut join_table = select(&Atom::merge(left, right), &cartesian_product);
-    join_table = join_table.project(&Atom::union(left, right));
-    join_table
+    let build = JoinBuild::new(left, right, right_table);
+    if !build.is_spilled() {
+        return build.probe(left_table);
+    }
+    let name = format!("{}_{}", left_table.name, right_table.name);
+    Stream::scan(left_table).probe(build).into_table(name)
+}
+
+/// The right side of a join, hashed once so that many left tables, such as the batches of a
+/// stream, can be probed against it. When it does not fit in the memory budget, it is split by key
+/// into partitions spilled to disk, a single one for a cartesian product.
+pub(crate) struct JoinBuild {
+    left: Atom,
+    right: Atom,
+    keys: JoinKeys,
+    side: BuildSide,
+}
+
+enum BuildSide {
+    Memory(HashTable),
+    Spilled(Vec<SpillFile>),
+}
+
+/// The key columns of the two sides of a join, and the row format they are hashed in.
+struct JoinKeys {
+    columns: Vec<(usize, usize)>,
+    converter: RowConverter,
+}
+
+impl JoinKeys {
+    /// The keys of a left table, or nothing for a cartesian product.
+    fn left(&self, table: &Table) -> Option<Rows> {
+        self.convert(table, self.columns.iter().map(|(left, _)| *left).collect())
+    }
+
+    /// The keys of a right table, or nothing for a cartesian product.
+    fn right(&self, table: &Table) -> Option<Rows> {
+        self.convert(
+            table,
+            self.columns.iter().map(|(_, right)| *right).collect(),
+        )
+    }
+
+    fn convert(&self, table: &Table, columns: Vec<usize>) -> Option<Rows> {
+        if columns.is_empty() {
+            return None;
+        }
+        let keys = table.projection(&columns).data;
+        Some(self.converter.convert_columns(keys.columns()).unwrap())
+    }
+
+    /// Writes `tables` to disk, split into partitions by the hash of the `keys` they give.
+    fn spill(
+        &self,
+        schema: &SchemaRef,
+        tables: impl IntoIterator<Item = Table>,
+        keys: impl Fn(&Table) -> Option<Rows>,
+    ) -> Vec<SpillFile> {
+        let mut writers = if self.columns.is_empty() {
+            vec![SpillWriter::new(schema)]
+        } else {
+            spill::writers(schema)
+        };
+        for table in tables {
+            let parts = match keys(&table) {
+                Some(keys) => spill::partition(&table.data, &keys),
+                None => vec![table.data],
+            };
+            for (writer, part) in writers.iter_mut().zip(parts) {
+                writer.write(&part);
+            }
+        }
+        writers.into_iter().map(SpillWriter::finish).collect()
+    }
+}
+
+/// A table with the indices of its rows grouped by key, except for a cartesian product.
+struct HashTable {
+    table: Table,
+    buckets: HashMap<Box<[u8]>, Vec<u32>>,
+    _reservation: Reservation,
 }
 
+impl HashTable {
+    fn new(table: Table, keys: Option<&Rows>, reservation: Reservation) -> Self {
+        let mut buckets: HashMap<Box<[u8]>, Vec<u32>> = HashMap::new();
+        for (index, row) in keys.into_iter().flat_map(Rows::iter).enumerate() {
+            buckets
+                .entry(row.as_ref().into())
+                .or_default()
+                .push(index as u32);
+        }
+        HashTable {
+            table,
+            buckets,
+            _reservation: reservation,
+        }
+    }
+
+    /// Bytes taken by the hash table of `table`, whose key columns are `keys`.
+    fn size(table: &Table, keys: Option<&Rows>) -> usize {
+        let entry = size_of::<Box<[u8]>>() + size_of::<Vec<u32>>() + size_of::<u32>();
+        table.memory_size() + keys.map_or(0, |keys| keys.size() + keys.num_rows() * entry)
+    }
+
+    /// Pairs the rows of `left`, whose keys are `probe`, with the rows of equal keys.
+    fn pairs(&self, left: &Table, probe: &Rows) -> Table {
+        let parts = parallel::map(&parallel::partitions(probe.num_rows()), |range| {
+            let mut left_indices = vec![];
+            let mut right_indices: Vec<u32> = vec![];
+            for row in range.clone() {
+                if row % CHECK_ROWS == 0 {
+                    interrupt::check();
+                }
+                if let Some(matches) = self.buckets.get(probe.row(row).as_ref()) {
+                    left_indices.extend(std::iter::repeat_n(row as u32, matches.len()));
+                    right_indices.extend(matches);
+                }
+            }
+            (left_indices, right_indices)
+        });
+        let (left_indices, right_indices): (Vec<_>, Vec<_>) = parts.into_iter().unzip();
+        pair_rows(
+            left,
+            &self.table,
+            &UInt32Array::from(left_indices.concat()),
+            &UInt32Array::from(right_indices.concat()),
+        )
+    }
+}
+
+impl JoinBuild {
+    pub(crate) fn new(left: &Atom, right: &Atom, right_table: &Table) -> Self {
+        let columns = join_keys(left, right);
+        let keys = JoinKeys {
+            converter: key_converter(columns.len()),
+            columns,
+        };
+        let build = keys.right(right_table);
+        let mut reservation = Reservation::new();
+        let side = if reservation.try_grow(HashTable::size(right_table, build.as_ref())) {
+            BuildSide::Memory(HashTable::new(
+                right_table.clone(),
+                build.as_ref(),
+                reservation,
+            ))
+        } else {
+            info!("spilling the build side of the join with {} to disk", right);
+            let schema = right_table.data.schema();
+            BuildSide::Spilled(
+                keys.spill(&schema, [right_table.clone()], |table| keys.right(table)),
+            )
+        };
+        JoinBuild {
+            left: left.clone(),
+            right: right.clone(),
+            keys,
+            side,
+        }
+    }
+
+    pub(crate) fn is_spilled(&self) -> bool {
+        matches!(self.side, BuildSide::Spilled(_))
+    }
+
+    /// The schema of the joined tables, for left tables of schema `left`.
+    pub(crate) fn schema(&self, left: SchemaRef) -> SchemaRef {
+        let right = match &self.side {
+            BuildSide::Memory(hash_table) => hash_table.table.data.schema(),
+            BuildSide::Spilled(files) => files[0].schema(),
+        };
+        let empty = |schema| Table {
+            name: String::new(),
+            data: RecordBatch::new_empty(schema),
+        };
+        let pairs = cartesian_product(&empty(left), &empty(right));
+        self.finish(&pairs).data.schema()
+    }
+
+    /// Joins `left_table` with the right side, which must be in memory.
+    pub(crate) fn probe(&self, left_table: &Table) -> Table {
+        profile::measure(Operator::Join, left_table.data.num_rows(), || {
+            interrupt::check();
+            let BuildSide::Memory(hash_table) = &self.side else {
+                unreachable!("spilled build sides are probed by partition")
+            };
+            self.finish(&self.pairs(hash_table, left_table))
+        })
+    }
+
+    /// Joins the left tables with a spilled right side, as a grace hash join: the left tables are
+    /// spilled into partitions too, which are joined one at a time as the results are pulled.
+    pub(crate) fn probe_spilled(
+        self,
+        schema: SchemaRef,
+        left_tables: impl Iterator<Item = Table> + Send + 'static,
+    ) -> impl Iterator<Item = Table> + Send + 'static {
+        std::iter::once((self, left_tables)).flat_map(move |(build, left_tables)| {
+            let left_files = build
+                .keys
+                .spill(&schema, left_tables, |table| build.keys.left(table));
+            let build = Arc::new(build);
+            left_files
+                .into_iter()
+                .enumerate()
+                .flat_map(move |(index, left_file)| build.clone().join_partitions(index, left_file))
+        })
+    }
+
+    /// Joins a left partition with the right partition `index`, hashed in blocks that fit in the
+    /// memory budget, reading the left partition back once per block.
+    fn join_partitions(
+        self: Arc<Self>,
+        index: usize,
+        left_file: SpillFile,
+    ) -> impl Iterator<Item = Table> + Send + 'static {
+        let BuildSide::Spilled(right_files) = &self.side else {
+            unreachable!("only spilled build sides are probed by partition")
+        };
+        let build = self.clone();
+        let blocks = right_files[index].blocks(move |data| {
+            let table = Table {
+                name: String::new(),
+                data: data.clone(),
+            };
+            HashTable::size(&table, build.keys.right(&table).as_ref())
+        });
+        blocks.flat_map(move |block| {
+            let right = Table {
+                name: self.right.relation_name.clone(),
+                data: block.data,
+            };
+            let keys = self.keys.right(&right);
+            let hash_table = HashTable::new(right, keys.as_ref(), block.reservation);
+            let build = self.clone();
+            left_file.batches().map(move |data| {
+                let left = Table {
+                    name: String::new(),
+                    data,
+                };
+                build.finish(&build.pairs(&hash_table, &left))
+            })
+        })
+    }
+
+    /// Pairs the rows of `left_table` with the rows of `hash_table` whose keys are equal.
+    fn pairs(&self, hash_table: &HashTable, left_table: &Table) -> Table {
+        match self.keys.left(left_table) {
+            Some(probe) => hash_table.pairs(left_table, &probe),
+            None => cartesian_product(left_table, &hash_table.table),
+        }
+    }
+
+    /// Checks the constants and repeated variables on the pairs and keeps one column per term.
+    fn finish(&self, pairs: &Table) -> Table {
+        let join_table = select(&Atom::merge(&self.left, &self.right), pairs);
+        join_table.project(&Atom::union(&self.left, &self.right))
+    }
+}
+
+/// Positions in `left` and in `right` of every variable the two atoms share.
+fn join_keys(left: &Atom, right: &Atom) -> Vec<(usize, usize)> {
+    left.shared_variables(right)
+        .iter()
+        .map(|variable| {
+            let position = |atom: &Atom| atom.terms.iter().position(|term| term == variable);
+            (position(left).unwrap(), position(right).unwrap())
+        })
+        .collect()
+}
+
+fn key_converter(columns: usize) -> RowConverter {
+    RowConverter::new(vec![SortField::new(DataType::Utf8); columns]).unwrap()
+}
+
+/// [`select`], looking up in `indexes` the constant matching the fewest rows instead of scanning
+/// its column.
+pub(crate) fn select_indexed(query: &Atom, table: &Table, indexes: &[Arc<Index>]) -> Table {
+    profile::measure(Operator::Select, table.data.num_rows(), || {
+        let rows = query
+            .terms
+            .iter()
+            .enumerate()
+            .filter_map(|(column, term)| match term {
+                Constant(constant) => indexes
+                    .iter()
+                    .find(|index| index.column() == column && index.covers(table))
+                    .map(|index| index.lookup(constant)),
+                Variable(_) => None,
+            })
+            .min_by_key(|rows| rows.len());
+        match rows {
+            Some(rows) => select(query, &take_rows(table, &UInt32Array::from(rows))),
+            None => select(query, table),
+        }
+    })
+}
+
+/// Keeps the rows of `table` holding the constants of `query`, and equal values in the columns of
+/// its repeated variables. A table with nothing to check is returned as is, sharing its columns.
 pub fn select(query: &Atom, table: &Table) -> Table {
+    let filtered =
+        query.terms.iter().enumerate().any(|(index, term)| {
+            matches!(term, Constant(_)) || query.terms[..index].contains(term)
+        });
+    if !filtered {
+        return table.clone();
+    }
+    profile::measure(Operator::Select, table.data.num_rows(), || {
+        select_unmeasured(query, table)
+    })
+}
+
+fn select_unmeasured(query: &Atom, table: &Table) -> Table {
     let mut filter = BooleanArray::from(vec![true; table.get_data().num_rows()]);
     for (index, term) in query.terms.iter().enumerate() {
         match term {
@@ -65,7 +466,52 @@ pub fn select(query: &Atom, table: &Table) -> Table {
     }
 }
 
+/// The rows of `table` at `indices`, in that order.
+fn take_rows(table: &Table, indices: &UInt32Array) -> Table {
+    let columns = table
+        .data
+        .columns()
+        .iter()
+        .map(|column| take(column, indices, None).unwrap())
+        .collect();
+    let options = RecordBatchOptions::new().with_row_count(Some(indices.len()));
+    Table {
+        name: table.name.clone(),
+        data: RecordBatch::try_new_with_options(table.data.schema(), columns, &options).unwrap(),
+    }
+}
+
 fn cartesian_product(left: &Table, right: &Table) -> Table {
+    // pair every left row with every right row: left row i is repeated once per right row,
+    // while the right rows are cycled once per left row
+    let left_rows = left.data.num_rows() as u32;
+    let right_rows = right.data.num_rows() as u32;
+    let check_every = (CHECK_ROWS / right_rows.max(1) as usize).max(1) as u32;
+    let mut left_indices = vec![];
+    let mut right_indices = vec![];
+    for i in 0..left_rows {
+        if i % check_every == 0 {
+            interrupt::check();
+        }
+        left_indices.extend(std::iter::repeat_n(i, right_rows as usize));
+        right_indices.extend(0..right_rows);
+    }
+    pair_rows(
+        left,
+        right,
+        &UInt32Array::from(left_indices),
+        &UInt32Array::from(right_indices),
+    )
+}
+
+/// The table whose row `i` is row `left_indices[i]` of `left` followed by row `right_indices[i]`
+/// of `right`.
+fn pair_rows(
+    left: &Table,
+    right: &Table,
+    left_indices: &UInt32Array,
+    right_indices: &UInt32Array,
+) -> Table {
     let schema = Schema::new(
         [
             left.data
@@ -84,47 +530,17 @@ fn cartesian_product(left: &Table, right: &Table) -> Table {
         ]
         .concat(),
     );
-    if left.is_empty() || right.is_empty() {
-        let d = RecordBatch::new_empty(Arc::new(schema));
-
-        return Table {
-            name: format!("{}_{}", left.name, right.name),
-            data: d,
-        };
-    }
-
-    let mut new_left_columns: Vec<ArrayRef> = vec![];
+    let mut columns: Vec<ArrayRef> = vec![];
     for column in left.data.columns() {
-        let new_column = array_multiply(column, right.data.num_rows());
-
-        new_left_columns.push(new_column);
+        columns.push(take(column, left_indices, None).unwrap());
     }
-    let mut new_right_columns = vec![];
     for column in right.data.columns() {
-        let new_column = array_multiply(column, left.data.num_rows());
-
-        new_right_columns.push(new_column);
+        columns.push(take(column, right_indices, None).unwrap());
     }
-    let data = RecordBatch::try_new(
-        Arc::new(schema),
-        [
-            new_left_columns.into_iter().collect::<Vec<_>>(),
-            new_right_columns.into_iter().collect::<Vec<_>>(),
-        ]
-        .concat(),
-    )
-    .unwrap();
+    let options = RecordBatchOptions::new().with_row_count(Some(left_indices.len()));
+    let data = RecordBatch::try_new_with_options(Arc::new(schema), columns, &options).unwrap();
     Table {
         name: format!("{}_{}", left.name, right.name),
         data,
     }
 }
-
-fn array_multiply(array: &ArrayRef, times: usize) -> ArrayRef {
-    arrow_select::concat::concat(
-        &std::iter::repeat(array.as_ref())
-            .take(times)
-            .collect::<Vec<_>>(),
-    )
-    .unwrap()
-}
@@ -0,0 +1,30 @@
+use std::cell::RefCell;
+use std::thread::LocalKey;
+
+/// Puts back the value a [`with`] replaced, also when the call unwinds.
+struct Restore<V: 'static> {
+    key: &'static LocalKey<RefCell<V>>,
+    previous: Option<V>,
+}
+
+impl<V> Drop for Restore<V> {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            self.key.set(previous);
+        }
+    }
+}
+
+/// Runs `f` with `value` in the thread-local `key`.
+pub(crate) fn with<V, T>(key: &'static LocalKey<RefCell<V>>, value: V, f: impl FnOnce() -> T) -> T {
+    let _restore = Restore {
+        key,
+        previous: Some(key.replace(value)),
+    };
+    f()
+}
+
+/// The value of the thread-local `key`, to hand over to another thread.
+pub(crate) fn get<V: Clone>(key: &'static LocalKey<RefCell<V>>) -> V {
+    key.with(|value| value.borrow().clone())
+}
@@ -0,0 +1,146 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use arrow::array::{RecordBatch, RecordBatchOptions, UInt32Array};
+use arrow::compute::take;
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use arrow_row::Rows;
+use arrow_schema::SchemaRef;
+use arrow_select::concat::concat_batches;
+
+use crate::data_structure::memory::Reservation;
+
+/// Number of partitions an operator splits its input into when it spills.
+pub(crate) const SPILL_PARTITIONS: usize = 16;
+
+static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary Arrow IPC file being written.
+pub(crate) struct SpillWriter {
+    file: SpillFile,
+    writer: FileWriter<File>,
+}
+
+impl SpillWriter {
+    pub(crate) fn new(schema: &SchemaRef) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "query-engine-{}-{}.arrow",
+            std::process::id(),
+            NEXT_FILE.fetch_add(1, Ordering::SeqCst)
+        ));
+        let file = File::create(&path).expect("cannot create spill file");
+        SpillWriter {
+            file: SpillFile {
+                path,
+                schema: schema.clone(),
+            },
+            writer: FileWriter::try_new(file, schema).unwrap(),
+        }
+    }
+
+    pub(crate) fn write(&mut self, batch: &RecordBatch) {
+        if batch.num_rows() > 0 {
+            self.writer.write(batch).unwrap();
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> SpillFile {
+        self.writer.finish().unwrap();
+        self.file
+    }
+}
+
+/// A finished temporary Arrow IPC file, removed from disk when dropped.
+pub(crate) struct SpillFile {
+    path: PathBuf,
+    schema: SchemaRef,
+}
+
+impl SpillFile {
+    pub(crate) fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Reads the batches back one at a time.
+    pub(crate) fn batches(&self) -> impl Iterator<Item = RecordBatch> + Send + 'static {
+        let file = File::open(&self.path).expect("cannot open spill file");
+        FileReader::try_new(file, None)
+            .unwrap()
+            .map(|batch| batch.unwrap())
+    }
+
+    /// Reads the batches back in blocks that fit in the memory budget, by the `size` of each batch.
+    /// A batch too large on its own still makes a block, without a reservation.
+    pub(crate) fn blocks(
+        &self,
+        size: impl Fn(&RecordBatch) -> usize + Send + 'static,
+    ) -> impl Iterator<Item = Block> + Send + 'static {
+        let schema = self.schema.clone();
+        let mut batches = self.batches().peekable();
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            let mut block = vec![batches.next()?];
+            let mut reservation = Reservation::new();
+            if reservation.try_grow(size(&block[0])) {
+                while let Some(batch) = batches.next_if(|batch| reservation.try_grow(size(batch))) {
+                    block.push(batch);
+                }
+            }
+            offset += block.len();
+            Some(Block {
+                offset: offset - block.len(),
+                data: concat_batches(&schema, &block).unwrap(),
+                reservation,
+            })
+        })
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Consecutive batches of a spill file, as one, with their share of the memory budget.
+pub(crate) struct Block {
+    /// Number of batches of the file before the block.
+    pub(crate) offset: usize,
+    pub(crate) data: RecordBatch,
+    pub(crate) reservation: Reservation,
+}
+
+/// One writer per partition.
+pub(crate) fn writers(schema: &SchemaRef) -> Vec<SpillWriter> {
+    (0..SPILL_PARTITIONS)
+        .map(|_| SpillWriter::new(schema))
+        .collect()
+}
+
+/// Splits `batch` into [`SPILL_PARTITIONS`] batches by the hash of `keys`, the row format of its
+/// key columns, so that rows with equal keys always end up in partitions with the same index.
+pub(crate) fn partition(batch: &RecordBatch, keys: &Rows) -> Vec<RecordBatch> {
+    let mut indices = vec![vec![]; SPILL_PARTITIONS];
+    for (index, key) in keys.iter().enumerate() {
+        let mut hasher = DefaultHasher::new();
+        key.as_ref().hash(&mut hasher);
+        indices[hasher.finish() as usize % SPILL_PARTITIONS].push(index as u32);
+    }
+    indices
+        .into_iter()
+        .map(|indices| {
+            let indices = UInt32Array::from(indices);
+            let columns = batch
+                .columns()
+                .iter()
+                .map(|column| take(column, &indices, None).unwrap())
+                .collect();
+            let options = RecordBatchOptions::new().with_row_count(Some(indices.len()));
+            RecordBatch::try_new_with_options(batch.schema(), columns, &options).unwrap()
+        })
+        .collect()
+}
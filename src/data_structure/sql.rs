@@ -0,0 +1,675 @@
+use std::collections::HashMap;
+
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::character::complete::multispace0;
+use nom::combinator::{map, recognize};
+use nom::multi::many0;
+use nom::sequence::{delimited, preceded, tuple};
+use nom::IResult;
+
+use crate::data_structure::query::{Atom, Query, Term};
+use crate::data_structure::reader;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Number(String),
+    Symbol(String),
+}
+
+fn lex_word(input: &str) -> IResult<&str, Token> {
+    map(
+        take_while1(|c: char| c.is_alphanumeric() || c == '_'),
+        |word: &str| {
+            if word.chars().all(|c| c.is_ascii_digit() || c == '.') {
+                Token::Number(word.to_string())
+            } else {
+                Token::Word(word.to_string())
+            }
+        },
+    )(input)
+}
+
+fn lex_number(input: &str) -> IResult<&str, Token> {
+    map(
+        recognize(tuple((
+            take_while1(|c: char| c.is_ascii_digit()),
+            tag("."),
+            take_while(|c: char| c.is_ascii_digit()),
+        ))),
+        |number: &str| Token::Number(number.to_string()),
+    )(input)
+}
+
+/// A string literal; a quote inside it is written twice.
+fn lex_text(input: &str) -> IResult<&str, Token> {
+    map(
+        delimited(
+            tag("'"),
+            recognize(many0(alt((tag("''"), take_while1(|c: char| c != '\''))))),
+            tag("'"),
+        ),
+        |text: &str| Token::Text(text.replace("''", "'")),
+    )(input)
+}
+
+fn lex_symbol(input: &str) -> IResult<&str, Token> {
+    map(
+        alt((
+            tag("<="),
+            tag(">="),
+            tag("<>"),
+            tag("!="),
+            tag("="),
+            tag("<"),
+            tag(">"),
+            tag(","),
+            tag("."),
+            tag("*"),
+            tag("("),
+            tag(")"),
+            tag(";"),
+        )),
+        |symbol: &str| Token::Symbol(symbol.to_string()),
+    )(input)
+}
+
+fn lex(input: &str) -> Result<Vec<Token>, String> {
+    let token = alt((lex_text, lex_number, lex_word, lex_symbol));
+    match tuple((many0(preceded(multispace0, token)), multispace0))(input) {
+        Ok(("", (tokens, _))) => Ok(tokens),
+        Ok((rest, _)) => Err(format!("unexpected input at: {}", rest)),
+        Err(_) => Err("could not read the statement".to_string()),
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) | Token::Number(word) | Token::Symbol(word) => word.clone(),
+        Token::Text(text) => format!("'{}'", text),
+    }
+}
+
+/// A column reference, `alias.column` or a bare `column`. In the select list it may be given a
+/// label with `AS`, which then names its variable.
+#[derive(Clone, Debug)]
+struct ColumnRef {
+    alias: Option<String>,
+    column: String,
+    label: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+enum Operand {
+    Column(ColumnRef),
+    Constant(String),
+}
+
+#[derive(Debug)]
+struct Select {
+    columns: Option<Vec<ColumnRef>>,
+    from: Vec<(String, String)>,
+    conditions: Vec<(Operand, Operand)>,
+}
+
+struct SqlParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl SqlParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if s == symbol)
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if !self.is_keyword(keyword) {
+            return Err(format!("expected {}", keyword));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if !self.is_symbol(symbol) {
+            return Err(format!("expected {}", symbol));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn identifier(&mut self) -> Result<String, String> {
+        let name = match self.peek() {
+            // `_` starts the anonymous variables of Datalog
+            Some(Token::Word(word)) if word.starts_with('_') => {
+                return Err(format!("names cannot start with _: {}", word))
+            }
+            Some(Token::Word(word)) if !is_reserved(word) => word.to_lowercase(),
+            Some(token) => return Err(format!("expected a name, found {}", describe(token))),
+            None => return Err("unexpected end of statement".to_string()),
+        };
+        self.position += 1;
+        Ok(name)
+    }
+
+    /// Why the statement is not a conjunctive query, when the parser stopped on such a construct.
+    fn unsupported(&self) -> Option<String> {
+        let token = self.peek()?;
+        let word = |position: Option<usize>| match position.and_then(|p| self.tokens.get(p)) {
+            Some(Token::Word(word)) => word.to_uppercase(),
+            _ => String::new(),
+        };
+        let reason = match token {
+            Token::Symbol(symbol) if symbol == "(" => {
+                match (
+                    word(self.position.checked_sub(1)),
+                    word(Some(self.position + 1)),
+                ) {
+                    (_, next) if next == "SELECT" => "nested queries are not conjunctive queries",
+                    (function, _)
+                        if ["COUNT", "SUM", "AVG", "MIN", "MAX"].contains(&&*function) =>
+                    {
+                        "aggregation is outside the conjunctive fragment"
+                    }
+                    _ => return None,
+                }
+            }
+            Token::Symbol(symbol)
+                if ["<", ">", "<=", ">=", "<>", "!="].contains(&symbol.as_str()) =>
+            {
+                "only equality comparisons can be expressed"
+            }
+            Token::Word(word) => match word.to_uppercase().as_str() {
+                "SELECT" | "IN" | "EXISTS" => "nested queries are not conjunctive queries",
+                "OR" => "disjunctions (OR) are not conjunctive, write one query per disjunct",
+                "NOT" | "EXCEPT" => "negation has no conjunctive query equivalent",
+                "UNION" | "INTERSECT" => {
+                    "set operations combine several queries, write one query each"
+                }
+                "LIKE" | "BETWEEN" | "IS" => "only equality comparisons can be expressed",
+                "GROUP" | "HAVING" => "aggregation is outside the conjunctive fragment",
+                "ORDER" | "LIMIT" | "OFFSET" => "conjunctive queries compute unordered sets",
+                "LEFT" | "RIGHT" | "FULL" | "OUTER" | "CROSS" | "NATURAL" => {
+                    "only inner joins can be expressed"
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(format!(
+            "unsupported SQL near {}: {}",
+            describe(token),
+            reason
+        ))
+    }
+
+    fn column_ref(&mut self) -> Result<ColumnRef, String> {
+        let name = self.identifier()?;
+        if self.is_symbol(".") {
+            self.position += 1;
+            let column = self.identifier()?;
+            return Ok(ColumnRef {
+                alias: Some(name),
+                column,
+                label: None,
+            });
+        }
+        Ok(ColumnRef {
+            alias: None,
+            column: name,
+            label: None,
+        })
+    }
+
+    fn selected_column(&mut self) -> Result<ColumnRef, String> {
+        let mut column = self.column_ref()?;
+        if self.is_keyword("AS") {
+            self.position += 1;
+            column.label = Some(self.identifier()?);
+        }
+        Ok(column)
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.peek() {
+            Some(Token::Text(text)) | Some(Token::Number(text)) => {
+                let constant = text.clone();
+                self.position += 1;
+                Ok(Operand::Constant(constant))
+            }
+            _ => Ok(Operand::Column(self.column_ref()?)),
+        }
+    }
+
+    fn condition(&mut self) -> Result<(Operand, Operand), String> {
+        let left = self.operand()?;
+        self.expect_symbol("=")?;
+        let right = self.operand()?;
+        Ok((left, right))
+    }
+
+    fn conditions(&mut self, conditions: &mut Vec<(Operand, Operand)>) -> Result<(), String> {
+        conditions.push(self.condition()?);
+        while self.is_keyword("AND") {
+            self.position += 1;
+            conditions.push(self.condition()?);
+        }
+        Ok(())
+    }
+
+    fn table(&mut self) -> Result<(String, String), String> {
+        let relation = self.identifier()?;
+        if self.is_keyword("AS") {
+            self.position += 1;
+            return Ok((relation, self.identifier()?));
+        }
+        let alias = match self.peek() {
+            // without AS, a join keyword starts the next FROM item instead of naming this one
+            Some(Token::Word(word)) if !is_reserved(word) && !is_join(word) => self.identifier()?,
+            _ => relation.clone(),
+        };
+        Ok((relation, alias))
+    }
+
+    fn select(&mut self) -> Result<Select, String> {
+        self.expect_keyword("SELECT")?;
+        if self.is_keyword("DISTINCT") {
+            self.position += 1;
+        }
+        let columns = if self.is_symbol("*") {
+            self.position += 1;
+            None
+        } else {
+            let mut columns = vec![self.selected_column()?];
+            while self.is_symbol(",") {
+                self.position += 1;
+                columns.push(self.selected_column()?);
+            }
+            Some(columns)
+        };
+        self.expect_keyword("FROM")?;
+        let mut from = vec![self.table()?];
+        let mut conditions = vec![];
+        loop {
+            if self.is_symbol(",") {
+                self.position += 1;
+                from.push(self.table()?);
+            } else if self.is_keyword("JOIN") || self.is_keyword("INNER") {
+                if self.is_keyword("INNER") {
+                    self.position += 1;
+                }
+                self.expect_keyword("JOIN")?;
+                from.push(self.table()?);
+                self.expect_keyword("ON")?;
+                self.conditions(&mut conditions)?;
+            } else {
+                break;
+            }
+        }
+        if self.is_keyword("WHERE") {
+            self.position += 1;
+            self.conditions(&mut conditions)?;
+        }
+        if self.is_symbol(";") {
+            self.position += 1;
+        }
+        if let Some(token) = self.peek() {
+            return Err(format!("unexpected {}", describe(token)));
+        }
+        Ok(Select {
+            columns,
+            from,
+            conditions,
+        })
+    }
+}
+
+fn is_reserved(word: &str) -> bool {
+    [
+        "SELECT",
+        "DISTINCT",
+        "FROM",
+        "WHERE",
+        "AND",
+        "AS",
+        "JOIN",
+        "INNER",
+        "ON",
+        "OR",
+        "NOT",
+        "IN",
+        "EXISTS",
+        "LIKE",
+        "BETWEEN",
+        "UNION",
+        "INTERSECT",
+        "EXCEPT",
+        "GROUP",
+        "HAVING",
+        "ORDER",
+        "LIMIT",
+        "OFFSET",
+    ]
+    .contains(&word.to_uppercase().as_str())
+}
+
+fn is_join(word: &str) -> bool {
+    ["LEFT", "RIGHT", "FULL", "OUTER", "CROSS", "NATURAL"].contains(&word.to_uppercase().as_str())
+}
+
+/// Compiles a SELECT-FROM-WHERE statement whose WHERE clause is a conjunction of equalities into
+/// the equivalent conjunctive query, with one atom per FROM item and one head term per selected
+/// column. Statements outside that fragment are rejected with the reason why. A column compared
+/// with two different constants gives a query without answers.
+pub fn parse_sql(input: &str) -> Result<Query, String> {
+    let mut parser = SqlParser {
+        tokens: lex(input)?,
+        position: 0,
+    };
+    let select = parser
+        .select()
+        .map_err(|error| parser.unsupported().unwrap_or(error))?;
+    Translation::new(&select)?.query(&select)
+}
+
+/// Columns of the FROM items, grouped into classes of columns that WHERE makes equal.
+struct Translation {
+    schemas: Vec<(String, String, Vec<String>)>,
+    classes: HashMap<(String, String), usize>,
+    parent: Vec<usize>,
+}
+
+impl Translation {
+    fn new(select: &Select) -> Result<Self, String> {
+        let mut translation = Translation {
+            schemas: vec![],
+            classes: HashMap::new(),
+            parent: vec![],
+        };
+        for (relation, alias) in &select.from {
+            if translation.schemas.iter().any(|(_, a, _)| a == alias) {
+                return Err(format!("alias {} is used twice", alias));
+            }
+            let Some(schema) = reader::schema(relation) else {
+                return Err(format!("unknown relation {}", relation));
+            };
+            let columns = schema
+                .fields()
+                .iter()
+                .map(|field| field.name().clone())
+                .collect::<Vec<_>>();
+            for column in &columns {
+                let class = translation.parent.len();
+                translation.parent.push(class);
+                translation
+                    .classes
+                    .insert((alias.clone(), column.clone()), class);
+            }
+            translation
+                .schemas
+                .push((relation.clone(), alias.clone(), columns));
+        }
+        Ok(translation)
+    }
+
+    fn resolve(&self, column: &ColumnRef) -> Result<usize, String> {
+        let candidates = self
+            .schemas
+            .iter()
+            .filter(|(_, alias, columns)| {
+                column.alias.as_ref().is_none_or(|a| a == alias) && columns.contains(&column.column)
+            })
+            .collect::<Vec<_>>();
+        match candidates.as_slice() {
+            [(_, alias, _)] => Ok(self.find(self.classes[&(alias.clone(), column.column.clone())])),
+            [] => Err(format!("unknown column {}", display_column(column))),
+            _ => Err(format!("ambiguous column {}", column.column)),
+        }
+    }
+
+    fn find(&self, mut class: usize) -> usize {
+        while self.parent[class] != class {
+            class = self.parent[class];
+        }
+        class
+    }
+
+    fn query(mut self, select: &Select) -> Result<Query, String> {
+        let mut constants: Vec<(usize, String)> = vec![];
+        for (left, right) in &select.conditions {
+            match (left, right) {
+                (Operand::Column(left), Operand::Column(right)) => {
+                    let (left, right) = (self.resolve(left)?, self.resolve(right)?);
+                    self.parent[left] = right;
+                }
+                (Operand::Column(column), Operand::Constant(constant))
+                | (Operand::Constant(constant), Operand::Column(column)) => {
+                    let class = self.resolve(column)?;
+                    constants.push((class, constant.clone()));
+                }
+                (Operand::Constant(left), Operand::Constant(right)) => {
+                    return Err(format!(
+                        "comparison '{}' = '{}' involves no column",
+                        left, right
+                    ))
+                }
+            }
+        }
+        // a class may be compared with several constants, directly or through merged classes;
+        // when they differ, the class keeps the first and no row can match
+        let mut bound: HashMap<usize, String> = HashMap::new();
+        let mut contradicted = vec![];
+        for (class, constant) in constants {
+            let class = self.find(class);
+            match bound.get(&class) {
+                Some(other) if *other != constant => contradicted.push(class),
+                _ => {
+                    bound.insert(class, constant);
+                }
+            }
+        }
+
+        let selected = match &select.columns {
+            Some(columns) => columns.clone(),
+            None => self
+                .schemas
+                .iter()
+                .flat_map(|(_, alias, columns)| {
+                    columns.iter().map(|column| ColumnRef {
+                        alias: Some(alias.clone()),
+                        column: column.clone(),
+                        label: None,
+                    })
+                })
+                .collect(),
+        };
+        let mut names: HashMap<usize, String> = HashMap::new();
+        let mut head = vec![];
+        for column in &selected {
+            let class = self.resolve(column)?;
+            match bound.get(&class) {
+                Some(constant) => head.push(Term::Constant(constant.clone())),
+                None => {
+                    let name = self.name(&mut names, class, column);
+                    head.push(Term::Variable(name));
+                }
+            }
+        }
+
+        let mut body = vec![];
+        let mut negated = vec![];
+        let mut anonymous = 0;
+        for (relation, alias, columns) in self.schemas.clone() {
+            let mut terms = vec![];
+            let mut is_contradicted = false;
+            for column in &columns {
+                let class = self.find(self.classes[&(alias.clone(), column.clone())]);
+                is_contradicted |= contradicted.contains(&class);
+                let is_shared = self
+                    .classes
+                    .values()
+                    .filter(|c| self.find(**c) == class)
+                    .count()
+                    > 1
+                    || names.contains_key(&class);
+                let term = match bound.get(&class) {
+                    Some(constant) => Term::Constant(constant.clone()),
+                    None if is_shared => {
+                        let reference = ColumnRef {
+                            alias: Some(alias.clone()),
+                            column: column.clone(),
+                            label: None,
+                        };
+                        Term::Variable(self.name(&mut names, class, &reference))
+                    }
+                    None => {
+                        anonymous += 1;
+                        Term::Variable(format!("_{}", anonymous))
+                    }
+                };
+                terms.push(term);
+            }
+            let atom = Atom {
+                relation_name: relation,
+                terms,
+            };
+            // the atom negated as well never matches, so the query is always empty
+            if is_contradicted && negated.is_empty() {
+                negated.push(atom.clone());
+            }
+            body.push(atom);
+        }
+        Ok(Query {
+            head: Atom {
+                relation_name: "answer".to_string(),
+                terms: head,
+            },
+            body,
+            negated,
+        })
+    }
+
+    /// The variable standing for a class: named after the label or the first column it is
+    /// referenced by, qualified by the alias when another class already took that name.
+    fn name(&self, names: &mut HashMap<usize, String>, class: usize, column: &ColumnRef) -> String {
+        if let Some(name) = names.get(&class) {
+            return name.clone();
+        }
+        let mut name = column.label.clone().unwrap_or(column.column.clone());
+        if names.values().any(|n| *n == name) {
+            name = format!(
+                "{}_{}",
+                column.alias.clone().unwrap_or_default(),
+                column.column
+            );
+        }
+        while names.values().any(|n| *n == name) {
+            name.push('_');
+        }
+        names.insert(class, name.clone());
+        name
+    }
+}
+
+fn display_column(column: &ColumnRef) -> String {
+    match &column.alias {
+        Some(alias) => format!("{}.{}", alias, column.column),
+        None => column.column.clone(),
+    }
+}
+
+/// Writes `query` as an equivalent SQL statement over the relations of the database on disk, to
+/// cross-check answers against other databases. Every atom becomes a FROM item, shared variables
+/// and constants become WHERE equalities, and negated atoms become NOT EXISTS subqueries.
+pub fn to_sql(query: &Query) -> Result<String, String> {
+    let mut bindings: HashMap<Term, String> = HashMap::new();
+    let mut from = vec![];
+    let mut conditions = vec![];
+    for (index, atom) in query.body.iter().enumerate() {
+        let alias = format!("t{}", index + 1);
+        bind_atom(atom, &alias, &mut bindings, &mut conditions)?;
+        from.push(format!("{} {}", atom.relation_name, alias));
+    }
+    for (index, atom) in query.negated.iter().enumerate() {
+        let alias = format!("n{}", index + 1);
+        let mut inner = vec![];
+        bind_atom(atom, &alias, &mut bindings.clone(), &mut inner)?;
+        let mut subquery = format!("SELECT 1 FROM {} {}", atom.relation_name, alias);
+        if !inner.is_empty() {
+            subquery = format!("{} WHERE {}", subquery, inner.join(" AND "));
+        }
+        conditions.push(format!("NOT EXISTS ({})", subquery));
+    }
+    let mut select = vec![];
+    for term in &query.head.terms {
+        match term {
+            Term::Variable(name) => match bindings.get(term) {
+                Some(column) => select.push(format!("{} AS {}", column, name)),
+                None => return Err(format!("head variable {} does not occur in the body", name)),
+            },
+            Term::Constant(constant) => select.push(sql_string(constant)),
+        }
+    }
+    if select.is_empty() {
+        select.push("1".to_string());
+    }
+    let mut sql = format!(
+        "SELECT DISTINCT {} FROM {}",
+        select.join(", "),
+        from.join(", ")
+    );
+    if !conditions.is_empty() {
+        sql = format!("{} WHERE {}", sql, conditions.join(" AND "));
+    }
+    Ok(sql)
+}
+
+/// Binds the variables of `atom` to the columns of `alias`; variables already bound to another
+/// column and constants turn into equalities.
+fn bind_atom(
+    atom: &Atom,
+    alias: &str,
+    bindings: &mut HashMap<Term, String>,
+    conditions: &mut Vec<String>,
+) -> Result<(), String> {
+    let Some(schema) = reader::schema(&atom.relation_name) else {
+        return Err(format!("unknown relation {}", atom.relation_name));
+    };
+    if schema.fields().len() != atom.terms.len() {
+        return Err(format!(
+            "{} expects {} terms, got {}",
+            atom.relation_name,
+            schema.fields().len(),
+            atom.terms.len()
+        ));
+    }
+    for (term, field) in atom.terms.iter().zip(schema.fields()) {
+        let column = format!("{}.{}", alias, field.name());
+        match term {
+            Term::Constant(constant) => {
+                conditions.push(format!("{} = {}", column, sql_string(constant)))
+            }
+            Term::Variable(_) => match bindings.get(term) {
+                Some(bound) => conditions.push(format!("{} = {}", column, bound)),
+                None => {
+                    bindings.insert(term.clone(), column);
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+fn sql_string(constant: &str) -> String {
+    format!("'{}'", constant.replace('\'', "''"))
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use arrow::array::Array;
+
+use crate::data_structure::table::Table;
+
+/// Number of most common values kept per column.
+pub const MOST_COMMON_VALUES: usize = 10;
+
+/// Number of buckets of the histogram of a column.
+pub const HISTOGRAM_BUCKETS: usize = 10;
+
+/// What the estimates know about a table.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableStatistics {
+    pub rows: usize,
+    pub columns: Vec<ColumnStatistics>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColumnStatistics {
+    /// Number of distinct non-null values.
+    pub distinct: usize,
+    pub nulls: usize,
+    /// The values occurring more than once, most common first, with their number of rows.
+    pub most_common: Vec<(String, usize)>,
+    /// The other non-null values.
+    pub histogram: Histogram,
+}
+
+/// An equi-depth histogram: the sorted values split into buckets of about as many rows.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Histogram {
+    pub buckets: Vec<Bucket>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bucket {
+    /// The largest value of the bucket; the smallest is above the upper bound of the previous one.
+    pub upper: String,
+    pub rows: usize,
+    pub distinct: usize,
+}
+
+impl TableStatistics {
+    pub fn compute(table: &Table) -> Self {
+        let columns = (0..table.get_data().num_columns())
+            .map(|index| ColumnStatistics::compute(table, index))
+            .collect();
+        TableStatistics {
+            rows: table.get_data().num_rows(),
+            columns,
+        }
+    }
+
+    /// Estimated fraction of the rows whose column `column` equals `value`.
+    pub fn equality_selectivity(&self, column: usize, value: &str) -> f64 {
+        if self.rows == 0 {
+            return 0.0;
+        }
+        let statistics = &self.columns[column];
+        if let Some((_, rows)) = statistics.most_common.iter().find(|(v, _)| v == value) {
+            return *rows as f64 / self.rows as f64;
+        }
+        match statistics.histogram.bucket_of(value) {
+            Some(bucket) => bucket.rows as f64 / bucket.distinct as f64 / self.rows as f64,
+            None => 0.0,
+        }
+    }
+
+    /// Estimated fraction of the rows whose columns `left` and `right` are equal.
+    pub fn column_equality_selectivity(&self, left: usize, right: usize) -> f64 {
+        join_selectivity(&self.columns[left], &self.columns[right])
+    }
+}
+
+impl ColumnStatistics {
+    fn compute(table: &Table, index: usize) -> Self {
+        let column = table.get_column(index).unwrap();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for value in column.iter().flatten() {
+            *counts.entry(value).or_default() += 1;
+        }
+        let mut values = counts.into_iter().collect::<Vec<_>>();
+        let distinct = values.len();
+        // most common first, ties broken by value so that the statistics are deterministic
+        values.sort_by(|(a, a_rows), (b, b_rows)| b_rows.cmp(a_rows).then(a.cmp(b)));
+        let common = values
+            .iter()
+            .take(MOST_COMMON_VALUES)
+            .take_while(|(_, rows)| *rows > 1)
+            .count();
+        let rest = values.split_off(common);
+        ColumnStatistics {
+            distinct,
+            nulls: column.null_count(),
+            most_common: values
+                .into_iter()
+                .map(|(value, rows)| (value.to_string(), rows))
+                .collect(),
+            histogram: Histogram::new(rest),
+        }
+    }
+}
+
+impl Histogram {
+    fn new(mut values: Vec<(&str, usize)>) -> Self {
+        values.sort();
+        let rows = values.iter().map(|(_, rows)| rows).sum::<usize>();
+        let depth = rows.div_ceil(HISTOGRAM_BUCKETS).max(1);
+        let mut buckets: Vec<Bucket> = vec![];
+        for (value, rows) in values {
+            match buckets.last_mut() {
+                Some(bucket) if bucket.rows < depth => {
+                    bucket.upper = value.to_string();
+                    bucket.rows += rows;
+                    bucket.distinct += 1;
+                }
+                _ => buckets.push(Bucket {
+                    upper: value.to_string(),
+                    rows,
+                    distinct: 1,
+                }),
+            }
+        }
+        Histogram { buckets }
+    }
+
+    /// The bucket whose range holds `value`, if any.
+    pub fn bucket_of(&self, value: &str) -> Option<&Bucket> {
+        self.buckets
+            .iter()
+            .find(|bucket| value <= bucket.upper.as_str())
+    }
+}
+
+/// Estimated fraction of the pairs of rows of two columns that are equal: every value of the
+/// column with fewer distinct values is assumed to occur in the other one.
+pub fn join_selectivity(left: &ColumnStatistics, right: &ColumnStatistics) -> f64 {
+    1.0 / left.distinct.max(right.distinct).max(1) as f64
+}
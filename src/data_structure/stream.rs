@@ -0,0 +1,286 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use arrow::array::RecordBatch;
+use arrow_row::{RowConverter, Rows, SortField};
+use arrow_schema::SchemaRef;
+use arrow_select::concat::concat_batches;
+use arrow_select::filter::filter_record_batch;
+use log::info;
+
+use crate::data_structure::interrupt;
+use crate::data_structure::memory::Reservation;
+use crate::data_structure::profile;
+use crate::data_structure::query::{Atom, Term};
+use crate::data_structure::relational_algebra::{self, JoinBuild};
+use crate::data_structure::spill::{self, SpillFile, SpillWriter};
+use crate::data_structure::table::Table;
+
+/// Largest number of rows in a batch of a stream.
+pub const BATCH_SIZE: usize = 8192;
+
+/// A pull-based pipeline of operators over Arrow record batches of at most [`BATCH_SIZE`] rows.
+/// Nothing is computed until batches are pulled, and every operator works on one batch at a time,
+/// so a pipeline only holds the batches in flight and the build sides of its joins.
+pub struct Stream {
+    schema: SchemaRef,
+    batches: Box<dyn Iterator<Item = RecordBatch> + Send>,
+}
+
+impl Iterator for Stream {
+    type Item = RecordBatch;
+
+    fn next(&mut self) -> Option<RecordBatch> {
+        interrupt::check();
+        self.batches.next()
+    }
+}
+
+impl Stream {
+    pub fn new(
+        schema: SchemaRef,
+        batches: impl Iterator<Item = RecordBatch> + Send + 'static,
+    ) -> Self {
+        Stream {
+            schema,
+            batches: Box::new(batches),
+        }
+    }
+
+    /// The rows of `table`, as zero-copy slices of its data.
+    pub fn scan(table: &Table) -> Self {
+        let data = table.get_data();
+        let rows = data.num_rows();
+        let slices = (0..rows)
+            .step_by(BATCH_SIZE)
+            .map(move |start| data.slice(start, BATCH_SIZE.min(rows - start)));
+        Stream::new(table.get_data().schema(), slices)
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Applies `operator` to every batch, profiled for the node the stream was built for. The
+    /// schema is the one `operator` gives to an empty batch.
+    fn map(self, operator: impl Fn(&Table) -> Table + Send + 'static) -> Self {
+        let empty = Table {
+            name: String::new(),
+            data: RecordBatch::new_empty(self.schema.clone()),
+        };
+        let schema = profile::without(|| operator(&empty).get_data().schema());
+        let context = profile::current();
+        let batches = self.batches.map(move |data| {
+            let table = Table {
+                name: String::new(),
+                data,
+            };
+            profile::with(context.clone(), || operator(&table).get_data())
+        });
+        Stream::new(schema, batches.filter(|batch| batch.num_rows() > 0))
+    }
+
+    /// Keeps the rows matching the constants and repeated variables of `atom`.
+    pub fn select(self, atom: &Atom) -> Self {
+        let atom = atom.clone();
+        self.map(move |table| relational_algebra::select(&atom, table))
+    }
+
+    pub fn project(self, terms: &[Term]) -> Self {
+        let terms = terms.to_vec();
+        self.map(move |table| table.project(&terms))
+    }
+
+    /// Probes every batch, whose columns are the terms of `left`, against the table of `right`,
+    /// which is loaded in a hash table once. Output batches are split back to [`BATCH_SIZE`]. When
+    /// the hash table does not fit in the memory budget, both sides are partitioned on disk and
+    /// joined partition by partition once the whole input has been pulled.
+    pub fn join(self, left: &Atom, right: &Atom, right_table: &Table) -> Self {
+        self.probe(JoinBuild::new(left, right, right_table))
+    }
+
+    /// Probes every batch against the right side of a join already built, as [`Stream::join`].
+    pub(crate) fn probe(self, build: JoinBuild) -> Self {
+        if !build.is_spilled() {
+            return self.map(move |table| build.probe(table)).rebatch();
+        }
+        let schema = profile::without(|| build.schema(self.schema.clone()));
+        let left_tables = self.batches.map(|data| Table {
+            name: String::new(),
+            data,
+        });
+        let batches = build
+            .probe_spilled(self.schema, left_tables)
+            .map(|table| table.get_data())
+            .filter(|batch| batch.num_rows() > 0);
+        Stream::new(schema, batches).rebatch()
+    }
+
+    /// Removes duplicate rows, remembering the rows already sent. Once they no longer fit in the
+    /// memory budget, the rows not seen yet are partitioned on disk instead, and every partition
+    /// is deduplicated on its own, in blocks that fit, after the whole input has been pulled.
+    pub fn distinct(self) -> Self {
+        let schema = self.schema.clone();
+        let distinct = Distinct {
+            input: self.batches,
+            schema: schema.clone(),
+            converter: row_converter(&schema),
+            seen: HashSet::new(),
+            reservation: Reservation::new(),
+            writers: vec![],
+            spilled: None,
+        };
+        Stream::new(schema, distinct)
+    }
+
+    /// Splits batches larger than [`BATCH_SIZE`].
+    fn rebatch(self) -> Self {
+        let batches = self.batches.flat_map(|batch| {
+            let rows = batch.num_rows();
+            (0..rows)
+                .step_by(BATCH_SIZE)
+                .map(move |start| batch.slice(start, BATCH_SIZE.min(rows - start)))
+                .collect::<Vec<_>>()
+        });
+        Stream::new(self.schema, batches)
+    }
+
+    /// Pulls every batch into a single table.
+    pub fn into_table(self, name: String) -> Table {
+        let schema = self.schema.clone();
+        let batches = self.collect::<Vec<_>>();
+        Table {
+            name,
+            data: concat_batches(&schema, &batches).unwrap(),
+        }
+    }
+
+    /// Writes the rows as CSV, with a header, batch by batch as they are pulled. Returns the number
+    /// of rows written.
+    pub fn write_csv<W: Write>(self, writer: W) -> Result<usize, String> {
+        let mut writer = arrow_csv::WriterBuilder::new()
+            .with_header(true)
+            .build(writer);
+        // an empty first batch writes the header even when there are no rows
+        writer
+            .write(&RecordBatch::new_empty(self.schema.clone()))
+            .map_err(|error| error.to_string())?;
+        let mut rows = 0;
+        for batch in self {
+            rows += batch.num_rows();
+            writer.write(&batch).map_err(|error| error.to_string())?;
+        }
+        Ok(rows)
+    }
+}
+
+struct Distinct {
+    input: Box<dyn Iterator<Item = RecordBatch> + Send>,
+    schema: SchemaRef,
+    converter: RowConverter,
+    seen: HashSet<Box<[u8]>>,
+    reservation: Reservation,
+    /// The partitions the rows not seen yet go to, once spilling.
+    writers: Vec<SpillWriter>,
+    /// The rows of the spilled partitions, deduplicated once the input is exhausted.
+    spilled: Option<Box<dyn Iterator<Item = RecordBatch> + Send>>,
+}
+
+impl Distinct {
+    /// The rows of `batch` not sent yet, or nothing if they are spilled.
+    fn fresh_rows(&mut self, batch: RecordBatch) -> Option<RecordBatch> {
+        let rows = self.converter.convert_columns(batch.columns()).unwrap();
+        if self.writers.is_empty() && !self.reservation.try_grow(rows.size()) {
+            info!("spilling the rows of a deduplication to disk");
+            self.writers = spill::writers(&self.schema);
+        }
+        if self.writers.is_empty() {
+            let batch = unique_rows(&batch, &rows, &mut self.seen);
+            return (batch.num_rows() > 0).then_some(batch);
+        }
+        let filter = rows
+            .iter()
+            .map(|row| Some(!self.seen.contains(row.as_ref())))
+            .collect();
+        let batch = filter_record_batch(&batch, &filter).unwrap();
+        let keys = self.converter.convert_columns(batch.columns()).unwrap();
+        for (writer, part) in self.writers.iter_mut().zip(spill::partition(&batch, &keys)) {
+            writer.write(&part);
+        }
+        None
+    }
+}
+
+impl Iterator for Distinct {
+    type Item = RecordBatch;
+
+    fn next(&mut self) -> Option<RecordBatch> {
+        while let Some(batch) = self.input.next() {
+            if let Some(batch) = self.fresh_rows(batch) {
+                return Some(batch);
+            }
+        }
+        if !self.writers.is_empty() {
+            // equal rows are in the same partition, so partitions are deduplicated one at a time
+            self.seen = HashSet::new();
+            self.reservation = Reservation::new();
+            let writers = std::mem::take(&mut self.writers);
+            let partitions = writers
+                .into_iter()
+                .map(SpillWriter::finish)
+                .collect::<Vec<_>>();
+            let schema = self.schema.clone();
+            self.spilled =
+                Some(Box::new(partitions.into_iter().flat_map(
+                    move |partition| partition_rows(partition, &schema),
+                )));
+        }
+        self.spilled.as_mut()?.find(|batch| batch.num_rows() > 0)
+    }
+}
+
+/// The rows of a spilled partition without duplicates, a block at a time: the rows of a block are
+/// dropped when the batches before it, read back from disk, hold them.
+fn partition_rows(partition: SpillFile, schema: &SchemaRef) -> impl Iterator<Item = RecordBatch> {
+    let converter = row_converter(schema);
+    let sizes = row_converter(schema);
+    let blocks =
+        partition.blocks(move |batch| sizes.convert_columns(batch.columns()).unwrap().size());
+    blocks.map(move |block| {
+        let rows = converter.convert_columns(block.data.columns()).unwrap();
+        let mut seen = HashSet::new();
+        let first = rows
+            .iter()
+            .map(|row| seen.insert(Box::<[u8]>::from(row.as_ref())))
+            .collect::<Vec<_>>();
+        for batch in partition.batches().take(block.offset) {
+            for row in converter.convert_columns(batch.columns()).unwrap().iter() {
+                seen.remove(row.as_ref());
+            }
+        }
+        let filter = rows
+            .iter()
+            .zip(first)
+            .map(|(row, first)| Some(first && seen.contains(row.as_ref())))
+            .collect();
+        filter_record_batch(&block.data, &filter).unwrap()
+    })
+}
+
+fn row_converter(schema: &SchemaRef) -> RowConverter {
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| SortField::new(field.data_type().clone()))
+        .collect();
+    RowConverter::new(fields).unwrap()
+}
+
+/// Keeps the rows of `batch`, whose row format is `rows`, that are not in `seen`, and adds them.
+fn unique_rows(batch: &RecordBatch, rows: &Rows, seen: &mut HashSet<Box<[u8]>>) -> RecordBatch {
+    let filter = rows
+        .iter()
+        .map(|row| Some(seen.insert(row.as_ref().into())))
+        .collect();
+    filter_record_batch(batch, &filter).unwrap()
+}
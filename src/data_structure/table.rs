@@ -1,14 +1,21 @@
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
 use std::sync::Arc;
 
-use arrow::array::{Array, BooleanArray, RecordBatch, StringArray};
+use arrow::array::{Array, ArrayRef, BooleanArray, RecordBatch, RecordBatchOptions, StringArray};
 use arrow::util::pretty::pretty_format_batches;
 use arrow_ord::cmp::distinct;
-use arrow_schema::SchemaBuilder;
+use arrow_row::{Row, RowConverter, SortField};
+use arrow_schema::{DataType, Field, Schema, SchemaBuilder};
+use arrow_select::concat::concat_batches;
 use arrow_select::filter::filter_record_batch;
 
+use crate::data_structure::memory::Reservation;
+use crate::data_structure::profile::{self, Operator};
 use crate::data_structure::query::Term;
 use crate::data_structure::query::Term::{Constant, Variable};
+use crate::data_structure::statistics::TableStatistics;
+use crate::data_structure::stream::Stream;
 
 #[derive(Clone)]
 pub struct Table {
@@ -21,7 +28,7 @@ impl Display for Table {
         write!(
             f,
             "{}",
-            pretty_format_batches(&[self.data.clone()]).unwrap()
+            pretty_format_batches(std::slice::from_ref(&self.data)).unwrap()
         )
     }
 }
@@ -33,6 +40,38 @@ impl Table {
             data: RecordBatch::new_empty(Arc::new(SchemaBuilder::new().finish())),
         }
     }
+    /// An empty relation with one string column per name.
+    pub(crate) fn new_empty_relation(name: String, columns: &[String]) -> Self {
+        let fields = columns
+            .iter()
+            .map(|column| Field::new(column, DataType::Utf8, true))
+            .collect::<Vec<_>>();
+        Self {
+            name,
+            data: RecordBatch::new_empty(Arc::new(Schema::new(fields))),
+        }
+    }
+    /// A relation with one string column per name, holding `rows`.
+    pub(crate) fn from_rows(name: String, columns: &[String], rows: &[Vec<String>]) -> Self {
+        let fields = columns
+            .iter()
+            .map(|column| Field::new(column, DataType::Utf8, true))
+            .collect::<Vec<_>>();
+        let arrays = (0..columns.len())
+            .map(|index| {
+                let values = rows
+                    .iter()
+                    .map(|row| row[index].as_str())
+                    .collect::<Vec<_>>();
+                Arc::new(StringArray::from(values)) as ArrayRef
+            })
+            .collect::<Vec<_>>();
+        let options = RecordBatchOptions::new().with_row_count(Some(rows.len()));
+        let data =
+            RecordBatch::try_new_with_options(Arc::new(Schema::new(fields)), arrays, &options)
+                .unwrap();
+        Self { name, data }
+    }
     pub fn set_data(&mut self, data: RecordBatch) {
         self.data = data;
     }
@@ -49,9 +88,7 @@ impl Table {
             .downcast_ref::<StringArray>()
     }
     pub fn get_column_as_vec(&self, index: usize) -> Option<Vec<String>> {
-        let Some(column) = self.get_column(index) else {
-            return None;
-        };
+        let column = self.get_column(index)?;
         let vec = column
             .iter()
             .map(|x| match x {
@@ -62,6 +99,16 @@ impl Table {
         Some(vec)
     }
 
+    /// Row count, distinct and null counts, most common values and histograms of the columns.
+    pub fn statistics(&self) -> TableStatistics {
+        TableStatistics::compute(self)
+    }
+
+    /// Bytes taken by the columns of the table.
+    pub fn memory_size(&self) -> usize {
+        self.data.get_array_memory_size()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.data.num_rows() == 0
     }
@@ -76,6 +123,12 @@ impl Table {
     }
 
     pub fn project(&self, attr: &[Term]) -> Self {
+        profile::measure(Operator::Project, self.data.num_rows(), || {
+            self.project_unmeasured(attr)
+        })
+    }
+
+    fn project_unmeasured(&self, attr: &[Term]) -> Self {
         let mut indices = vec![];
         for (index, term) in attr.iter().enumerate() {
             match term {
@@ -92,6 +145,34 @@ impl Table {
         self.projection(&indices)
     }
 
+    /// Like [`Table::project`], but constants become columns holding the constant on every row.
+    pub(crate) fn project_with_constants(&self, terms: &[Term]) -> Self {
+        let rows = self.data.num_rows();
+        let mut fields = vec![];
+        let mut columns: Vec<ArrayRef> = vec![];
+        for term in terms {
+            match term {
+                Variable(name) => {
+                    let index = self.data.schema().index_of(name).unwrap();
+                    fields.push(self.data.schema().field(index).clone());
+                    columns.push(self.data.column(index).clone());
+                }
+                Constant(constant) => {
+                    fields.push(Field::new(constant, DataType::Utf8, true));
+                    columns.push(Arc::new(StringArray::from(vec![constant.as_str(); rows])));
+                }
+            }
+        }
+        let options = RecordBatchOptions::new().with_row_count(Some(rows));
+        let data =
+            RecordBatch::try_new_with_options(Arc::new(Schema::new(fields)), columns, &options)
+                .unwrap();
+        Table {
+            name: self.name.clone(),
+            data,
+        }
+    }
+
     pub fn intersection(&self, table: &Table) -> Table {
         let column = self.get_column(0).unwrap();
         let column_2 = table.get_column(0).unwrap();
@@ -120,4 +201,123 @@ impl Table {
             data,
         }
     }
+
+    /// Removes duplicate rows, keeping the first occurrence of each.
+    pub fn distinct(&self) -> Self {
+        if self.data.num_columns() == 0 {
+            return self.with_row_count(self.data.num_rows().min(1));
+        }
+        // the row format and the set of rows seen, estimated before converting anything
+        let size = self.memory_size() + self.data.num_rows() * size_of::<Row>();
+        let mut reservation = Reservation::new();
+        if !reservation.try_grow(size) {
+            // the rows seen do not fit in the memory budget: let the stream spill them
+            return Stream::scan(self).distinct().into_table(self.name.clone());
+        }
+        let rows = self
+            .row_converter()
+            .convert_columns(self.data.columns())
+            .unwrap();
+        let mut seen = HashSet::new();
+        let filter = rows
+            .iter()
+            .map(|row| Some(seen.insert(row)))
+            .collect::<BooleanArray>();
+        let data = filter_record_batch(&self.data, &filter).unwrap();
+        Table {
+            name: self.name.clone(),
+            data,
+        }
+    }
+
+    /// Appends the rows of `table`, matching its columns by position. No duplicates are removed.
+    pub fn union(&self, table: &Table) -> Self {
+        if self.data.num_columns() == 0 {
+            return self.with_row_count(self.data.num_rows() + table.data.num_rows());
+        }
+        let other =
+            RecordBatch::try_new(self.data.schema(), table.data.columns().to_vec()).unwrap();
+        let data = concat_batches(&self.data.schema(), &[self.data.clone(), other]).unwrap();
+        Table {
+            name: self.name.clone(),
+            data,
+        }
+    }
+
+    /// Keeps the rows that do not occur in `table`, matching columns by position.
+    pub fn difference(&self, table: &Table) -> Self {
+        if self.data.num_columns() == 0 {
+            let rows = if table.is_empty() {
+                self.data.num_rows()
+            } else {
+                0
+            };
+            return self.with_row_count(rows);
+        }
+        let filter = arrow::compute::not(&self.contains_rows(table)).unwrap();
+        self.filter(&filter)
+    }
+
+    /// For every row, whether it also occurs in `table`, matching columns by position.
+    pub(crate) fn contains_rows(&self, table: &Table) -> BooleanArray {
+        let converter = self.row_converter();
+        let existing = converter.convert_columns(table.data.columns()).unwrap();
+        let existing = existing.iter().collect::<HashSet<_>>();
+        let rows = converter.convert_columns(self.data.columns()).unwrap();
+        rows.iter()
+            .map(|row| Some(existing.contains(&row)))
+            .collect::<BooleanArray>()
+    }
+
+    pub(crate) fn filter(&self, filter: &BooleanArray) -> Self {
+        let data = filter_record_batch(&self.data, filter).unwrap();
+        Table {
+            name: self.name.clone(),
+            data,
+        }
+    }
+
+    fn row_converter(&self) -> RowConverter {
+        RowConverter::new(
+            self.data
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| SortField::new(field.data_type().clone()))
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    /// Renames the columns, in order, to `names`.
+    pub fn with_column_names(&self, names: &[String]) -> Self {
+        let fields = self
+            .data
+            .schema()
+            .fields()
+            .iter()
+            .zip(names)
+            .map(|(field, name)| field.as_ref().clone().with_name(name))
+            .collect::<Vec<_>>();
+        let options = RecordBatchOptions::new().with_row_count(Some(self.data.num_rows()));
+        let data = RecordBatch::try_new_with_options(
+            Arc::new(Schema::new(fields)),
+            self.data.columns().to_vec(),
+            &options,
+        )
+        .unwrap();
+        Table {
+            name: self.name.clone(),
+            data,
+        }
+    }
+
+    fn with_row_count(&self, row_count: usize) -> Self {
+        let options = RecordBatchOptions::new().with_row_count(Some(row_count));
+        let data = RecordBatch::try_new_with_options(self.data.schema(), vec![], &options).unwrap();
+        Table {
+            name: self.name.clone(),
+            data,
+        }
+    }
 }
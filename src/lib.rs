@@ -1,2 +1,3 @@
 pub mod assignment;
+pub mod batch;
 pub mod data_structure;
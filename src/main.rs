@@ -1,6 +1,13 @@
+use std::time::Duration;
+
 use query_engine_acq::assignment::{answers_query, bool_answer, is_acyclic};
-use query_engine_acq::data_structure::parser::parse_queries;
+use query_engine_acq::batch::{BatchExecutor, Failure};
+use query_engine_acq::data_structure::database::Database;
+use query_engine_acq::data_structure::memory;
+use query_engine_acq::data_structure::parser::try_parse_query;
+use query_engine_acq::data_structure::reader::shared_database;
 
+#[allow(dead_code)] // the fields are only read through `Debug`
 #[derive(Debug)]
 pub struct Answer {
     query_id: usize,
@@ -12,48 +19,92 @@ pub struct Answer {
     w: Vec<String>,
 }
 
-fn main() {
-    let query = parse_queries("input.txt");
-    let mut answers: Vec<Answer> = vec![];
-    for (id, q) in query.iter().enumerate() {
-        let is_acyclic = is_acyclic(q);
-        if !is_acyclic {
-            answers.push(Answer {
-                query_id: id + 1,
-                is_acyclic,
-                bool_answer: None,
-                x: vec![],
-                y: vec![],
-                z: vec![],
-                w: vec![],
-            });
-            continue;
-        }
-        let bool_answer = bool_answer(q);
-        if bool_answer.is_some() {
-            answers.push(Answer {
-                query_id: id + 1,
-                is_acyclic,
-                bool_answer,
-                x: vec![],
-                y: vec![],
-                z: vec![],
-                w: vec![],
-            });
-            continue;
-        }
-        let answer = answers_query(q);
-        answers.push(Answer {
-            query_id: id + 1,
+fn answer(query_id: usize, line: &str, database: &Database) -> Answer {
+    let q = &try_parse_query(line, database)
+        .unwrap_or_else(|error| panic!("Error parsing query: {}: {}", line, error));
+    let is_acyclic = is_acyclic(q);
+    if !is_acyclic {
+        return Answer {
+            query_id,
+            is_acyclic,
+            bool_answer: None,
+            x: vec![],
+            y: vec![],
+            z: vec![],
+            w: vec![],
+        };
+    }
+    let bool_answer = bool_answer(q, database);
+    if bool_answer.is_some() {
+        return Answer {
+            query_id,
             is_acyclic,
             bool_answer,
-            x: answer[0].clone(),
-            y: answer[1].clone(),
-            z: answer[2].clone(),
-            w: answer[3].clone(),
-        });
+            x: vec![],
+            y: vec![],
+            z: vec![],
+            w: vec![],
+        };
     }
-    for answer in answers {
-        println!("{:?}", answer);
+    let answer = answers_query(q, database);
+    Answer {
+        query_id,
+        is_acyclic,
+        bool_answer,
+        x: answer[0].clone(),
+        y: answer[1].clone(),
+        z: answer[2].clone(),
+        w: answer[3].clone(),
+    }
+}
+
+fn main() {
+    // RUST_LOG=info logs the join trees, the spills to disk and the rounds of Datalog programs
+    env_logger::init();
+    // a limit in bytes on the memory of joins and deduplications, past which they spill to disk
+    if let Ok(limit) = std::env::var("MEMORY_LIMIT") {
+        memory::set_limit(Some(
+            limit
+                .parse()
+                .expect("MEMORY_LIMIT must be a number of bytes"),
+        ));
+    }
+    let input = std::fs::read_to_string("input.txt").unwrap();
+    let lines = input
+        .lines()
+        .filter(|line| !line.is_empty())
+        .enumerate()
+        .collect::<Vec<_>>();
+    // `explain` prints the plan of every query instead of running it, and `explain analyze`
+    // runs it and prints the plan with the statistics of its operators
+    let arguments = std::env::args().skip(1).collect::<Vec<_>>();
+    if arguments.first().map(String::as_str) == Some("explain") {
+        let analyze = arguments.get(1).map(String::as_str) == Some("analyze");
+        let database = shared_database();
+        for (id, line) in &lines {
+            println!("query {}", id + 1);
+            match try_parse_query(line, &database) {
+                Ok(query) if analyze => println!("{}\n", query.explain_analyze(&database)),
+                Ok(query) => println!("{}\n", query.explain(&database)),
+                Err(error) => eprintln!("query {} failed: {}", id + 1, error),
+            }
+        }
+        return;
+    }
+    let mut executor = BatchExecutor::new(shared_database());
+    // a limit in milliseconds on the evaluation of every query
+    if let Ok(timeout) = std::env::var("QUERY_TIMEOUT_MS") {
+        let timeout = timeout.parse().expect("QUERY_TIMEOUT_MS must be a number");
+        executor = executor.with_timeout(Duration::from_millis(timeout));
+    }
+    let answers = executor.run(&lines, |(id, line), database| {
+        answer(id + 1, line, database)
+    });
+    for ((id, _), answer) in lines.iter().zip(answers) {
+        match answer {
+            Ok(answer) => println!("{:?}", answer),
+            Err(Failure::Error(error)) => eprintln!("query {} failed: {}", id + 1, error),
+            Err(interrupted) => println!("query {} {}", id + 1, interrupted),
+        }
     }
 }
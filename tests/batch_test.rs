@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use query_engine_acq::batch::{BatchExecutor, Failure};
+use query_engine_acq::data_structure::interrupt::CancellationToken;
+use query_engine_acq::data_structure::parser::parse_query;
+use query_engine_acq::data_structure::reader::shared_database;
+
+#[test]
+fn batch_keeps_the_input_order_and_isolates_failures() {
+    let queries = [
+        "Answer(x):-Categories(c,x).",
+        "Answer(x):-Styles(_,c,x),Categories(c,'British Ale').",
+        "Answer(x):-Unknown(x).",
+        "Answer(x,y):-Categories(x,y).",
+        "Answer(x):-Styles(x,_,_).",
+    ]
+    .map(parse_query);
+    let executor = BatchExecutor::with_threads(shared_database(), 3);
+    let results = executor.evaluate_all(&queries);
+    assert_eq!(results.len(), queries.len());
+    for (query, result) in queries.iter().zip(&results) {
+        if query.body[0].relation_name == "unknown" {
+            let failure = result.as_ref().err().unwrap();
+            assert_eq!(failure, &Failure::Error("Table not found".to_string()));
+            continue;
+        }
+        let expected = query.evaluate(executor.database());
+        let table = result.as_ref().unwrap();
+        assert_eq!(table.get_data().num_columns(), query.head.terms.len());
+        assert_eq!(table.get_data().num_rows(), expected.get_data().num_rows());
+    }
+}
+
+#[test]
+fn batch_jobs_are_stopped_by_their_timeout_or_token() {
+    let queries =
+        ["Answer(a,b,c):-Beers(a,_,_,_,_,_,_,_),Breweries(b,_,_,_,_,_,_,_,_,_,_),Styles(c,_,_)."]
+            .map(parse_query);
+    let executor =
+        BatchExecutor::with_threads(shared_database(), 2).with_timeout(Duration::from_millis(10));
+    let results = executor.evaluate_all(&queries);
+    assert_eq!(results[0].as_ref().err(), Some(&Failure::Timeout));
+
+    let token = CancellationToken::new();
+    token.cancel();
+    let executor = BatchExecutor::with_threads(shared_database(), 2).with_token(token);
+    let results = executor.evaluate_all(&queries);
+    assert_eq!(results[0].as_ref().err(), Some(&Failure::Cancelled));
+}
@@ -0,0 +1,14 @@
+use query_engine_acq::data_structure::table::Table;
+
+/// The rows of `table` as strings, sorted so that tables can be compared whatever their order.
+pub fn sorted_rows(table: &Table) -> Vec<Vec<String>> {
+    let data = table.get_data();
+    let columns = (0..data.num_columns())
+        .map(|column| table.get_column_as_vec(column).unwrap())
+        .collect::<Vec<_>>();
+    let mut rows = (0..data.num_rows())
+        .map(|row| columns.iter().map(|column| column[row].clone()).collect())
+        .collect::<Vec<_>>();
+    rows.sort();
+    rows
+}
@@ -0,0 +1,23 @@
+use std::sync::Arc;
+
+use query_engine_acq::data_structure::parser::parse_query;
+use query_engine_acq::data_structure::reader::{get_database_with_query, shared_database};
+
+#[test]
+fn database_is_loaded_once_and_viewed_per_query() {
+    let database = shared_database();
+    assert!(Arc::ptr_eq(&database, &shared_database()));
+
+    let query = parse_query("Answer(x):-Categories(c,x).");
+    let view = get_database_with_query(&query);
+    let renamed = view.get_table("categories").get_data();
+    let original = database.get_table("categories").get_data();
+    assert_eq!(renamed.schema().field(1).name(), "x");
+    assert_eq!(original.schema().field(1).name(), "cat_name");
+    assert!(Arc::ptr_eq(renamed.column(1), original.column(1)));
+    // relations the query does not use are shared as they are
+    assert!(Arc::ptr_eq(
+        view.get_table("beers").get_data().column(0),
+        database.get_table("beers").get_data().column(0)
+    ));
+}
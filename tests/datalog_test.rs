@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+
+use query_engine_acq::data_structure::database::Database;
+use query_engine_acq::data_structure::parser::{parse_program, parse_query};
+use query_engine_acq::data_structure::reader::get_database;
+
+#[test]
+fn idb_predicates_compose() {
+    let mut database = get_database();
+    let program = parse_program(
+        "Texan(w):-Breweries(w,u1,u2,u3,u4,'Texas',u5,u6,u7,u8,u9).\n\
+         Answer(b,w):-Beers(u1,w,b,u2,u3,u4,u5,u6),Texan(w).",
+        &database,
+    );
+    program.evaluate(&mut database).unwrap();
+
+    let direct = parse_query(
+        "Answer(b,w):-Beers(u1,w,b,u2,u3,u4,u5,u6),Breweries(w,u7,u8,u9,u10,'Texas',u11,u12,u13,u14,u15).",
+    )
+    .evaluate(&get_database());
+    let expected = (0..direct.get_data().num_rows())
+        .map(|i| {
+            (
+                direct.get_column_as_vec(0).unwrap()[i].clone(),
+                direct.get_column_as_vec(1).unwrap()[i].clone(),
+            )
+        })
+        .collect::<HashSet<_>>();
+
+    let answer = database.get_table("answer");
+    let beers = answer.get_column_as_vec(0).unwrap();
+    let styles = answer.get_column_as_vec(1).unwrap();
+    let actual = beers.into_iter().zip(styles).collect::<HashSet<_>>();
+    assert!(!actual.is_empty());
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn transitive_closure_reaches_fixpoint() {
+    // style ids and category ids share the same numbers, which turns styles.csv into a graph
+    let mut database = get_database();
+    let program = parse_program(
+        "Link(x,y):-Styles(x,y,u).\n\
+         Reach(x,y):-Link(x,y).\n\
+         Reach(x,z):-Link(x,y),Reach(y,z).",
+        &database,
+    );
+    let iterations = program.evaluate(&mut database).unwrap();
+    let last = iterations.last().unwrap();
+    assert_eq!(last.deltas, vec![("reach".to_string(), 0)]);
+
+    let link = database.get_table("link");
+    let edges = link
+        .get_column_as_vec(0)
+        .unwrap()
+        .into_iter()
+        .zip(link.get_column_as_vec(1).unwrap())
+        .collect::<HashSet<_>>();
+    let mut expected = edges.clone();
+    loop {
+        let mut next = expected.clone();
+        for (x, y) in &edges {
+            for (u, z) in &expected {
+                if y == u {
+                    next.insert((x.clone(), z.clone()));
+                }
+            }
+        }
+        if next.len() == expected.len() {
+            break;
+        }
+        expected = next;
+    }
+
+    let reach = database.get_table("reach");
+    let actual = reach
+        .get_column_as_vec(0)
+        .unwrap()
+        .into_iter()
+        .zip(reach.get_column_as_vec(1).unwrap())
+        .collect::<HashSet<_>>();
+    assert!(expected.len() > edges.len());
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn mutually_recursive_predicates_share_a_stratum() {
+    let program = parse_program(
+        "P(x):-Categories(x,y).\nQ(x):-P(x).\nP(x):-Q(x).\nR(x):-Q(x).",
+        &Database::default(),
+    );
+    assert_eq!(
+        program.strata(),
+        vec![
+            vec!["p".to_string(), "q".to_string()],
+            vec!["r".to_string()]
+        ]
+    );
+}
+
+#[test]
+fn negation_keeps_breweries_without_location() {
+    let mut database = get_database();
+    let program = parse_program(
+        "Located(b):-Locations(u1,b,u2,u3,u4).\n\
+         Answer(b):-Breweries(b,x,u1,u2,u3,u4,u5,u6,u7,u8,u9), not Located(b).",
+        &database,
+    );
+    program.evaluate(&mut database).unwrap();
+
+    let located = database
+        .get_table("located")
+        .get_column_as_vec(0)
+        .unwrap()
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let expected = database
+        .get_table("breweries")
+        .get_column_as_vec(0)
+        .unwrap()
+        .into_iter()
+        .filter(|brewery| !located.contains(brewery))
+        .collect::<HashSet<_>>();
+    let actual = database
+        .get_table("answer")
+        .get_column_as_vec(0)
+        .unwrap()
+        .into_iter()
+        .collect::<HashSet<_>>();
+    assert!(!expected.is_empty());
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn programs_replace_derived_relations_but_not_base_relations() {
+    let mut database = get_database();
+    let texan = parse_program(
+        "Texan(w):-Breweries(w,u1,u2,u3,u4,'Texas',u5,u6,u7,u8,u9).",
+        &database,
+    );
+    texan.evaluate(&mut database).unwrap();
+    let first = database.get_table("texan").get_column_as_vec(0);
+    texan.evaluate(&mut database).unwrap();
+    assert_eq!(database.get_table("texan").get_column_as_vec(0), first);
+
+    let beers = parse_program(
+        "Answer(b):-Beers(u1,w,b,u2,u3,u4,u5,u6),Texan(w).",
+        &database,
+    );
+    beers.evaluate(&mut database).unwrap();
+    assert!(!database.get_table("answer").is_empty());
+
+    let redefined = parse_program("Beers(w):-Texan(w).", &database);
+    assert_eq!(
+        redefined.evaluate(&mut database).unwrap_err(),
+        "beers is a base relation of the database"
+    );
+}
+
+#[test]
+fn unsafe_and_unstratified_negation_is_rejected() {
+    let unsafe_rule = parse_program(
+        "Answer(x):-Categories(x,y), not Styles(z,x,y).",
+        &Database::default(),
+    );
+    assert_eq!(
+        unsafe_rule.evaluate(&mut get_database()).unwrap_err(),
+        "variable z of not styles does not occur in a positive atom"
+    );
+
+    let unstratified = parse_program(
+        "P(x):-Categories(x,y), not Q(x).\nQ(x):-P(x).",
+        &Database::default(),
+    );
+    assert_eq!(
+        unstratified.evaluate(&mut get_database()).unwrap_err(),
+        "program is not stratifiable: p depends negatively on q through recursion"
+    );
+}
+
+#[test]
+fn head_constants_fill_their_column() {
+    let mut database = get_database();
+    let program = parse_program(
+        "Tagged(s,'ale'):-Styles(s,c,u),Categories(c,'British Ale').\n\
+         Answer(s):-Tagged(s,'ale').",
+        &database,
+    );
+    program.evaluate(&mut database).unwrap();
+    let tagged = database.get_table("tagged");
+    assert!(!tagged.is_empty());
+    assert!(tagged
+        .get_column_as_vec(1)
+        .unwrap()
+        .iter()
+        .all(|tag| tag == "ale"));
+    assert_eq!(
+        database.get_table("answer").get_column_as_vec(0),
+        tagged.get_column_as_vec(0)
+    );
+}
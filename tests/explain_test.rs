@@ -0,0 +1,136 @@
+use query_engine_acq::data_structure::explain::{Fallback, Strategy};
+use query_engine_acq::data_structure::parser::parse_query;
+use query_engine_acq::data_structure::profile::{Operator, Phase};
+use query_engine_acq::data_structure::query::Term;
+use query_engine_acq::data_structure::reader::get_database;
+
+#[test]
+fn acyclic_queries_are_explained_along_their_cheapest_join_tree() {
+    let database = get_database();
+    let query = parse_query(
+        "Answer(x,y,z):-Beers(_,_,z,_,_,_,x,_),Styles(_,y,x),Categories(y,'British Ale').",
+    );
+    let plan = query.explain(&database);
+    let Strategy::Yannakakis(components) = &plan.strategy else {
+        panic!("expected Yannakakis, got {:?}", plan.strategy);
+    };
+    assert_eq!(components.len(), 1);
+    let component = &components[0];
+    let join_tree = query.cheapest_join_tree(&database).unwrap();
+    assert_eq!(component.join_tree, join_tree);
+    assert_eq!(component.post_order.len(), 2);
+    assert_eq!(component.pre_order.len(), 2);
+    assert_eq!(component.joins.len(), 2);
+    let root = component.join_tree.get_root();
+    assert_eq!(component.post_order.last().unwrap().0, *root);
+    assert_eq!(component.pre_order[0].1, *root);
+    let last = component.joins.last().unwrap();
+    assert_eq!(last.node.relation_name, root.relation_name);
+    for variable in ["x", "y", "z"] {
+        assert!(last
+            .projection
+            .contains(&Term::Variable(variable.to_string())));
+    }
+    let text = plan.to_string();
+    assert!(text.contains("semi-join pass (post-order)"));
+    assert!(text.contains("project on [x, y, z], distinct"));
+}
+
+#[test]
+fn cyclic_and_negated_queries_show_their_fallback() {
+    let database = get_database();
+    let cyclic =
+        parse_query("Answer(x,y,z):-Beers(_,_,z,_,_,_,x,_),Styles(_,y,x),Categories(y,z).");
+    let plan = cyclic.explain(&database);
+    let Strategy::LeftDeepJoin { reason, order } = &plan.strategy else {
+        panic!("expected a left-deep join, got {:?}", plan.strategy);
+    };
+    assert_eq!(*reason, Fallback::Cyclic);
+    assert_eq!(order, &cyclic.body);
+    assert!(plan
+        .to_string()
+        .contains("left-deep join, as the body is cyclic"));
+
+    let negated = parse_query("Answer(x,'ale'):-Styles(_,c,x),not Categories(c,'British Ale').");
+    let plan = negated.explain(&database);
+    assert_eq!(plan.anti_joins, negated.negated);
+    assert_eq!(plan.head, negated.head.terms);
+    assert_eq!(plan.query.head.terms.len(), 2);
+    assert!(plan
+        .to_string()
+        .contains("anti-join with categories(c,'British Ale')"));
+}
+
+#[test]
+fn negated_queries_are_explained_without_their_redundant_positive_atoms() {
+    let database = get_database();
+    let query =
+        parse_query("Answer(x):-Styles(_,c,x),Styles(_,_,x),not Categories(c,'British Ale').");
+    let plan = query.explain(&database);
+    assert_eq!(plan.removed, vec![query.body[1].clone()]);
+    assert_eq!(plan.query.body.len(), 1);
+    assert_eq!(plan.anti_joins, query.negated);
+    assert!(plan
+        .to_string()
+        .contains("removed as redundant: styles(_,_,x)"));
+}
+
+#[test]
+fn explain_analyze_reports_the_operators_of_every_node_and_phase() {
+    let database = get_database();
+    let query = parse_query(
+        "Answer(x,y,z):-Beers(_,_,z,_,_,_,x,_),Styles(_,y,x),Categories(y,'British Ale').",
+    );
+    let analysis = query.explain_analyze(&database);
+    assert_eq!(
+        analysis.answers.get_data().num_rows(),
+        query.evaluate(&database).get_data().num_rows()
+    );
+    let Strategy::Yannakakis(components) = &analysis.plan.strategy else {
+        panic!("expected Yannakakis, got {:?}", analysis.plan.strategy);
+    };
+    let join_tree = &components[0].join_tree;
+    let root = &join_tree.get_root().relation_name;
+    let semi_joins = analysis
+        .profile
+        .get(root, Phase::PostOrder, Operator::SemiJoin)
+        .unwrap();
+    assert_eq!(
+        semi_joins.calls,
+        join_tree.get_children(join_tree.get_root()).len()
+    );
+    assert!(semi_joins.rows_out <= semi_joins.rows_in);
+    // the root has a single child: its semi-join reads the root only, and its join probes the
+    // result of the child once, without counting the hash table built on it as a call
+    assert_eq!(
+        semi_joins.rows_in,
+        database.get_table(root).get_data().num_rows()
+    );
+    let joins = analysis
+        .profile
+        .get(root, Phase::Join, Operator::Join)
+        .unwrap();
+    assert_eq!(joins.calls, 1);
+    for node in join_tree.get_nodes() {
+        let phases = analysis
+            .profile
+            .node(&node.relation_name)
+            .map(|(phase, _, _)| phase)
+            .collect::<Vec<_>>();
+        assert!(phases.contains(&Phase::PostOrder));
+        assert!(phases.contains(&Phase::Join));
+        if node.relation_name != *root {
+            assert!(analysis
+                .profile
+                .get(&node.relation_name, Phase::PreOrder, Operator::SemiJoin)
+                .is_some());
+        }
+    }
+    let text = analysis.to_string();
+    assert!(text.contains("[pre-order] semi-join"));
+    assert!(text.ends_with(&format!(
+        "answers: {} rows in {:.3} ms",
+        analysis.answers.get_data().num_rows(),
+        analysis.time.as_secs_f64() * 1000.0
+    )));
+}
@@ -0,0 +1,50 @@
+use query_engine_acq::data_structure::hypergraph::Hypergraph;
+use query_engine_acq::data_structure::parser::parse_query;
+
+#[test]
+fn join_tree_exports_label_edges_with_shared_variables() {
+    let query = parse_query("Answer(x,z):-Categories(x,y),Categories(y,z).");
+    let join_tree = query.construct_join_tree().unwrap();
+
+    let dot = join_tree.to_dot();
+    assert!(dot.starts_with("digraph join_tree {"));
+    assert!(dot.contains("n0 [label=\"categories(x,y)\""));
+    assert!(dot.contains("n1 [label=\"categories(y,z)\""));
+    assert!(dot.contains("style=bold"));
+    assert!(dot.contains("[label=\"y\"];"));
+
+    let json = join_tree.to_json();
+    assert!(json.contains(
+        "{\"relation\":\"categories\",\"terms\":[\"x\",\"y\"],\"label\":\"categories(x,y)\"}"
+    ));
+    assert!(json.contains("\"shared\":[\"y\"]"));
+    assert!(!json.contains("\"root\":null"));
+}
+
+#[test]
+fn hypergraph_exports_overlapping_hyperedges() {
+    let query = parse_query("Answer(x):-Categories(x,y),Categories(y,'1'),Categories(z,_).");
+    let hypergraph = Hypergraph::new(&query);
+
+    assert_eq!(
+        hypergraph.to_dot(),
+        "graph hypergraph {\n    node [shape=box];\n    \
+         n0 [label=\"categories(x,y)\"];\n    \
+         n1 [label=\"categories(y,'1')\"];\n    \
+         n2 [label=\"categories(z,_)\"];\n    \
+         n0 -- n1 [label=\"y\"];\n}\n"
+    );
+    let json = hypergraph.to_json();
+    assert!(json.contains("\"vertices\":[\"x\",\"y\"]"));
+    assert!(json.contains("\"vertices\":[\"z\"]"));
+    assert!(json.ends_with("\"edges\":[{\"left\":0,\"right\":1,\"shared\":[\"y\"]}]}"));
+}
+
+#[test]
+fn hypergraph_exports_leave_out_shared_constants() {
+    let query = parse_query("Answer(x,y):-Categories(x,'1'),Categories(y,'1').");
+    let hypergraph = Hypergraph::new(&query);
+
+    assert!(!hypergraph.to_dot().contains(" -- "));
+    assert!(hypergraph.to_json().ends_with("\"edges\":[]}"));
+}
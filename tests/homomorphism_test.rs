@@ -0,0 +1,55 @@
+use query_engine_acq::data_structure::parser::parse_query;
+
+#[test]
+fn minimize_drops_redundant_atoms() {
+    let query = parse_query(
+        "Answer(x):-Beers(_,w,x,_,_,_,_,_),Beers(_,w,_,_,_,_,_,_),Breweries(w,_,_,_,_,_,_,_,_,_,_).",
+    );
+    let core = query.minimize();
+    assert_eq!(core.body.len(), 2);
+    assert_eq!(core.body[0], query.body[0]);
+    assert_eq!(core.body[1], query.body[2]);
+
+    // constants and head variables keep atoms in the core
+    let query = parse_query("Answer(x,y):-Categories(x,'1'),Categories(y,_).");
+    assert_eq!(query.minimize(), query);
+}
+
+#[test]
+fn minimize_can_make_a_cyclic_query_acyclic() {
+    let query =
+        parse_query("Answer(x):-Categories(x,y),Categories(y,z),Categories(z,x),Categories(x,x).");
+    assert!(!query.is_acyclic());
+    let core = query.minimize();
+    assert_eq!(core.to_string(), "answer(x):-categories(x,x).");
+    assert!(core.is_acyclic());
+}
+
+#[test]
+fn containment_and_equivalence() {
+    let brewed = parse_query("Answer(x):-Beers(_,w,x,_,_,_,_,_),Breweries(w,_,_,_,_,_,_,_,_,_,_).");
+    let texan =
+        parse_query("Answer(x):-Beers(_,w,x,_,_,_,_,_),Breweries(w,_,_,_,_,'Texas',_,_,_,_,_).");
+    let any_beer = parse_query("Answer(y):-Beers(_,_,y,_,_,_,_,_).");
+    assert!(texan.is_contained_in(&brewed));
+    assert!(!brewed.is_contained_in(&texan));
+    assert!(brewed.is_contained_in(&any_beer));
+    assert!(!any_beer.is_contained_in(&brewed));
+
+    let redundant = parse_query(
+        "Answer(x):-Beers(_,w,x,_,_,_,_,_),Beers(_,w,_,_,_,_,_,_),Breweries(w,_,_,_,_,_,_,_,_,_,_).",
+    );
+    assert!(redundant.is_equivalent_to(&brewed));
+    assert!(!texan.is_equivalent_to(&brewed));
+}
+
+#[test]
+fn containment_of_cyclic_queries() {
+    let triangle = parse_query("Answer(x):-Categories(x,y),Categories(y,z),Categories(z,x).");
+    let path = parse_query("Answer(x):-Categories(x,y),Categories(y,z).");
+    let loop_ = parse_query("Answer(x):-Categories(x,x).");
+    assert!(triangle.is_contained_in(&path));
+    assert!(!path.is_contained_in(&triangle));
+    assert!(loop_.is_contained_in(&triangle));
+    assert!(!triangle.is_contained_in(&loop_));
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::sync::Arc;
+
+use arrow::array::{Int64Array, RecordBatch};
+use arrow_schema::{DataType, Field, Schema};
+
+use query_engine_acq::data_structure::index::{Index, IndexKind};
+use query_engine_acq::data_structure::parser::parse_query;
+use query_engine_acq::data_structure::reader::{get_database, get_database_with_query};
+
+use common::sorted_rows;
+
+#[test]
+fn indexes_find_the_rows_scans_find() {
+    let database = get_database();
+    let styles = database.get_table("styles");
+    let categories = styles.get_column_as_vec(1).unwrap();
+    for kind in [IndexKind::Hash, IndexKind::Sorted] {
+        let index = Index::new(styles, 1, kind);
+        assert_eq!(index.kind(), kind);
+        assert!(index.covers(styles));
+        for value in ["1", "3", "11", "missing"] {
+            let expected = (0..categories.len() as u32)
+                .filter(|row| categories[*row as usize] == value)
+                .collect::<Vec<_>>();
+            assert_eq!(index.lookup(value), expected);
+        }
+    }
+
+    // sorted indexes compare typed columns in the order of their type
+    let mut numbers = styles.clone();
+    let schema = Schema::new(vec![Field::new("n", DataType::Int64, true)]);
+    let column = Int64Array::from(vec![Some(10), Some(9), None, Some(10), Some(100)]);
+    numbers.set_data(RecordBatch::try_new(Arc::new(schema), vec![Arc::new(column)]).unwrap());
+    let index = Index::new(&numbers, 0, IndexKind::Sorted);
+    assert_eq!(index.lookup("10"), vec![0, 3]);
+    assert_eq!(index.lookup("100"), vec![4]);
+    assert!(index.lookup("not a number").is_empty());
+    assert!(!index.covers(styles));
+}
+
+#[test]
+fn queries_give_the_same_answers_with_indexes() {
+    let queries = [
+        "Answer(x):-Styles(_,'1',x).",
+        "Answer(x,y):-Styles(s,c,x),Categories(c,y).",
+        "Answer(n):-Beers(_,b,n,_,_,_,_,_),Breweries(b,'Westmalle',_,_,_,_,_,_,_,_,_).",
+        "Answer(x):-Styles(s,c,x),Categories(c,'British Ale').",
+    ];
+    let mut indexed = get_database();
+    for name in indexed.table_names() {
+        for column in 0..indexed.get_table(&name).get_data().num_columns() {
+            let kind = if column % 2 == 0 {
+                IndexKind::Hash
+            } else {
+                IndexKind::Sorted
+            };
+            indexed.create_index(&name, column, kind);
+        }
+    }
+    for query in queries {
+        let query = parse_query(query);
+        let plain = query.evaluate(&get_database_with_query(&query));
+        let with_indexes = query.evaluate(&indexed);
+        assert_eq!(sorted_rows(&plain), sorted_rows(&with_indexes));
+    }
+
+    // renamed views share the indexed columns, replaced tables lose their indexes
+    let query = parse_query(queries[0]);
+    let view = indexed.view(&query);
+    assert!(view.indexes("styles")[1].covers(view.get_table("styles")));
+    let categories = indexed.get_table("categories").clone();
+    indexed.add_table(categories);
+    assert!(indexed.indexes("categories").is_empty());
+}
+
+#[test]
+fn semi_joins_look_up_the_index_of_the_reducing_relation() {
+    let mut database = get_database();
+    database.create_index("styles", 1, IndexKind::Hash);
+    // styles is a leaf below categories, and reduces it on its indexed category column
+    let query = parse_query("Answer(x):-Styles(_,c,x),Categories(c,'British Ale').");
+    let join_tree = query.cheapest_join_tree(&database).unwrap();
+    assert_eq!(join_tree.get_root(), &query.body[1]);
+    let plain = query.evaluate(&get_database_with_query(&query));
+    let with_index = query.evaluate(&database);
+    assert_eq!(sorted_rows(&plain), sorted_rows(&with_index));
+    assert!(database.indexes("styles")[0].lookups() > 0);
+}
@@ -0,0 +1,24 @@
+use std::collections::BTreeSet;
+
+use query_engine_acq::data_structure::parser::parse_query;
+use query_engine_acq::data_structure::reader::get_database_with_query;
+
+#[test]
+fn joins_pair_every_matching_row() {
+    // 1414 breweries and 1304 locations: row counts sharing a factor, which a cartesian product
+    // cycling both sides in lockstep only partly pairs
+    let query =
+        parse_query("Answer(l,b):-Breweries(b,n,a1,a2,c,s,z,y,p,w,d),Locations(l,b,la,lo,ac).");
+    let database = get_database_with_query(&query);
+    let answer = query.yannakakis(database);
+    // semi-joins keep every row once, however many rows it matches
+    assert_eq!(answer.get_data().num_rows(), 1298);
+    let locations = answer.get_column_as_vec(0).unwrap();
+    let breweries = answer.get_column_as_vec(1).unwrap();
+    let rows = locations
+        .into_iter()
+        .zip(breweries)
+        .collect::<BTreeSet<_>>();
+    assert_eq!(rows.len(), 1298);
+    assert!(rows.contains(&("1".to_string(), "1".to_string())));
+}
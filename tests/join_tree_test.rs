@@ -0,0 +1,152 @@
+use query_engine_acq::data_structure::cost::join_tree_cost;
+use query_engine_acq::data_structure::parser::parse_query;
+use query_engine_acq::data_structure::query::Atom;
+use query_engine_acq::data_structure::reader::{get_database, get_database_with_query};
+
+#[test]
+fn join_tree_follows_body_order() {
+    let query = parse_query("Answer(x):-Categories(x,y),Categories(y,z),Categories(z,w).");
+    let join_tree = query.construct_join_tree().unwrap();
+    assert_eq!(join_tree.get_root(), &query.body[2]);
+    assert_eq!(join_tree.get_parent(&query.body[0]), Some(&query.body[1]));
+    assert_eq!(join_tree.get_parent(&query.body[1]), Some(&query.body[2]));
+    for _ in 0..10 {
+        let again = query.construct_join_tree().unwrap();
+        assert_eq!(again.to_string(), join_tree.to_string());
+    }
+}
+
+#[test]
+fn join_tree_can_be_rooted_at_any_atom() {
+    let query = parse_query("Answer(x):-Categories(x,y),Categories(y,z),Categories(z,w).");
+    let join_tree = query.construct_join_tree_with_root(&query.body[0]).unwrap();
+    assert_eq!(join_tree.get_root(), &query.body[0]);
+    assert_eq!(join_tree.get_parent(&query.body[1]), Some(&query.body[0]));
+    assert_eq!(join_tree.get_parent(&query.body[2]), Some(&query.body[1]));
+
+    let middle = query.construct_join_tree_with_root(&query.body[1]).unwrap();
+    assert_eq!(middle.get_root(), &query.body[1]);
+    assert_eq!(middle.get_children(&query.body[1]).len(), 2);
+
+    let other = parse_query("Answer(x):-Beers(_,x,_,_,_,_,_,_).");
+    assert!(query
+        .construct_join_tree_with_root(&other.body[0])
+        .is_none());
+}
+
+#[test]
+fn cheapest_join_tree_is_rooted_where_the_head_variables_are() {
+    let database = get_database();
+    for (query, root) in [
+        (
+            "Answer(x):-Beers(_,v,x,_,_,_,_,_),Breweries(v,_,_,_,_,_,_,_,_,_,_).",
+            0,
+        ),
+        (
+            "Answer(n):-Beers(_,v,_,_,_,_,_,_),Breweries(v,n,_,_,_,_,_,_,_,_,_).",
+            1,
+        ),
+    ] {
+        let query = parse_query(query);
+        let join_trees = query.join_trees();
+        assert_eq!(join_trees.len(), 2);
+        let cheapest = query.cheapest_join_tree(&database).unwrap();
+        assert_eq!(cheapest.get_root(), &query.body[root]);
+        let cost = join_tree_cost(&query, &cheapest, &database);
+        for join_tree in &join_trees {
+            assert!(cost <= join_tree_cost(&query, join_tree, &database));
+        }
+    }
+}
+
+#[test]
+fn disconnected_bodies_are_evaluated_as_a_forest() {
+    let database = get_database();
+    let query = parse_query("Answer(x,y):-Categories(a,x),Styles(b,c,y).");
+    assert!(query.construct_join_tree().is_none());
+    let join_forest = query.construct_join_forest().unwrap();
+    assert_eq!(join_forest.trees.len(), 2);
+    assert_eq!(join_forest.trees[0].get_root(), &query.body[0]);
+    assert_eq!(join_forest.trees[1].get_root(), &query.body[1]);
+
+    let categories = parse_query("Answer(x):-Categories(a,x).").evaluate(&database);
+    let styles = parse_query("Answer(y):-Styles(b,c,y).").evaluate(&database);
+    let answer = query.evaluate(&database);
+    assert_eq!(
+        answer.get_data().num_rows(),
+        categories.get_data().num_rows() * styles.get_data().num_rows()
+    );
+    let answer = query.yannakakis(get_database_with_query(&query));
+    assert_eq!(
+        answer.get_data().num_rows(),
+        categories.get_data().num_rows() * styles.get_data().num_rows()
+    );
+
+    // a component without head variables only has to match
+    let query = parse_query("Answer(x):-Categories(a,x),Styles(b,'1',_).");
+    assert_eq!(query.evaluate(&database).get_data().num_rows(), 11);
+    let query = parse_query("Answer(x):-Categories(a,x),Styles(b,'none',_).");
+    assert!(query.evaluate(&database).is_empty());
+}
+
+#[test]
+fn single_atoms_have_a_join_tree() {
+    let query = parse_query("Answer(x):-Categories(a,x).");
+    let join_tree = query.construct_join_tree().unwrap();
+    assert_eq!(join_tree.get_root(), &query.body[0]);
+    let answer = query.yannakakis(get_database_with_query(&query));
+    assert_eq!(answer.get_data().num_rows(), 11);
+}
+
+#[test]
+fn traversals_visit_children_and_parents_in_order() {
+    let query = parse_query(
+        "Answer(x):-Categories(x,y),Categories(y,z),Categories(z,w),Categories(w,v),Categories(z,u).",
+    );
+    let join_tree = query.construct_join_tree_with_root(&query.body[2]).unwrap();
+    let position = |atom: &Atom| query.body.iter().position(|other| other == atom).unwrap();
+    let children = join_tree.get_children(&query.body[2]);
+    assert_eq!(children.len(), 3);
+    assert_eq!(join_tree.get_descendants(&query.body[2]).len(), 4);
+    assert_eq!(
+        join_tree.get_descendants(&query.body[1]),
+        vec![&query.body[0]]
+    );
+
+    let pre_order = join_tree.pre_order().map(position).collect::<Vec<_>>();
+    let post_order = join_tree.post_order().map(position).collect::<Vec<_>>();
+    assert_eq!(pre_order.len(), 5);
+    assert_eq!(pre_order[0], 2);
+    assert_eq!(post_order[4], 2);
+    for atom in &query.body {
+        if let Some(parent) = join_tree.get_parent(atom) {
+            let index = |order: &[usize], atom| order.iter().position(|i| *i == position(atom));
+            assert!(index(&pre_order, parent) < index(&pre_order, atom));
+            assert!(index(&post_order, parent) > index(&post_order, atom));
+        }
+    }
+}
+
+#[test]
+fn head_variables_from_several_children_are_joined() {
+    let query = parse_query("Answer(n,k,m):-Styles(_,c,n),Categories(c,k),Styles(_,c,m).");
+    let mut aliased = query.clone();
+    aliased.body[2].relation_name = "styles#2".to_string();
+    let database = get_database().bind(&query, &aliased);
+    let by_category =
+        parse_query("Answer(c,n):-Styles(_,c,n),Categories(c,_).").evaluate(&get_database());
+    let categories = by_category.get_column_as_vec(0).unwrap();
+    let mut expected = 0;
+    for category in &categories {
+        expected += categories.iter().filter(|other| *other == category).count();
+    }
+    let join_trees = aliased.join_trees();
+    assert!(join_trees
+        .iter()
+        .any(|join_tree| join_tree.get_children(join_tree.get_root()).len() == 2));
+    for join_tree in &join_trees {
+        let answer = aliased.yannakakis_with_join_tree(database.clone(), join_tree);
+        assert_eq!(answer.get_data().num_columns(), 3);
+        assert_eq!(answer.get_data().num_rows(), expected);
+    }
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use query_engine_acq::data_structure::memory;
+use query_engine_acq::data_structure::parser::parse_query;
+use query_engine_acq::data_structure::query::Term;
+use query_engine_acq::data_structure::reader::get_database;
+use query_engine_acq::data_structure::stream::{Stream, BATCH_SIZE};
+
+use common::sorted_rows;
+
+/// Held by the tests that change the memory limit, which is shared by the whole process.
+static LIMIT: Mutex<()> = Mutex::new(());
+
+/// Access to the memory limit, which is removed again when dropped, even if the test fails.
+struct Limit {
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Limit {
+    fn acquire() -> Self {
+        let lock = LIMIT.lock().unwrap_or_else(PoisonError::into_inner);
+        Limit { _lock: lock }
+    }
+
+    fn set(&self, bytes: usize) {
+        memory::set_limit(Some(bytes));
+    }
+}
+
+impl Drop for Limit {
+    fn drop(&mut self) {
+        memory::set_limit(None);
+    }
+}
+
+fn spill_files() -> usize {
+    let prefix = format!("query-engine-{}-", std::process::id());
+    std::fs::read_dir(std::env::temp_dir())
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_string_lossy().starts_with(&prefix)
+        })
+        .count()
+}
+
+#[test]
+fn joins_and_deduplications_spill_past_the_memory_limit() {
+    let limit = Limit::acquire();
+    let database = get_database();
+    let queries = [
+        "Answer(n,c,y):-Beers(_,v,n,_,_,_,_,_),Breweries(v,_,c,_,_,_,_,_,_,_,_),Locations(_,v,y,_,_).",
+        "Answer(x,k):-Styles(_,c,x),Categories(c,k).",
+    ]
+    .map(parse_query);
+    let in_memory = queries
+        .iter()
+        .map(|query| {
+            let evaluated = query.evaluate(&database);
+            let streamed = query.stream(&database).into_table("Answer".to_string());
+            (sorted_rows(&evaluated), sorted_rows(&streamed))
+        })
+        .collect::<Vec<_>>();
+
+    limit.set(1);
+    for (query, (evaluated, streamed)) in queries.iter().zip(&in_memory) {
+        assert_eq!(&sorted_rows(&query.evaluate(&database)), evaluated);
+        let stream = query.stream(&database);
+        assert_eq!(
+            &sorted_rows(&stream.into_table("Answer".to_string())),
+            streamed
+        );
+    }
+
+    assert_eq!(memory::used(), 0);
+    assert_eq!(spill_files(), 0);
+}
+
+#[test]
+fn partitions_larger_than_the_limit_are_read_back_in_blocks() {
+    let limit = Limit::acquire();
+    let database = get_database();
+    // the atoms share no variable, so they are joined by a cartesian product, which has no key to
+    // partition on, and every pair of a beer and its style is repeated once per category
+    let query = parse_query("Answer(x,b,s):-Categories(x,_),Beers(_,_,b,_,_,_,s,_).");
+    let pairs = [
+        Term::Variable("b".to_string()),
+        Term::Variable("s".to_string()),
+    ];
+    let product = query.evaluate(&database);
+    assert!(product.get_data().num_rows() > 2 * BATCH_SIZE);
+    let expected = (
+        sorted_rows(&product),
+        sorted_rows(&product.project(&pairs).distinct()),
+    );
+
+    // a few batches of a spilled partition fit in the limit, but not the whole partition
+    limit.set(1 << 16);
+    let product = query.evaluate(&database);
+    let distinct = Stream::scan(&product)
+        .project(&pairs)
+        .distinct()
+        .into_table("Answer".to_string());
+    assert_eq!((sorted_rows(&product), sorted_rows(&distinct)), expected);
+
+    assert_eq!(memory::used(), 0);
+    assert_eq!(spill_files(), 0);
+}
@@ -0,0 +1,141 @@
+use query_engine_acq::data_structure::hypergraph::Hypergraph;
+use query_engine_acq::data_structure::parser::{parse_program, parse_query, try_parse_query};
+use query_engine_acq::data_structure::query::Term;
+use query_engine_acq::data_structure::reader::get_database;
+
+#[test]
+fn anonymous_variables_are_fresh() {
+    let query = parse_query("Answer(x):-Categories(_,x),Styles(_,_,x).");
+    let anonymous = query
+        .body
+        .iter()
+        .flat_map(|atom| atom.terms.iter())
+        .filter(|term| term.is_anonymous())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        anonymous,
+        vec![
+            &Term::Variable("_1".to_string()),
+            &Term::Variable("_2".to_string()),
+            &Term::Variable("_3".to_string()),
+        ]
+    );
+
+    let hypergraph = Hypergraph::new(&query);
+    for vertices in hypergraph.hyperedges.values() {
+        assert!(vertices.iter().all(|term| !term.is_anonymous()));
+    }
+}
+
+#[test]
+fn anonymous_variables_do_not_join() {
+    let database = get_database();
+    let named = parse_query(
+        "Answer(x,y,z,w):-Beers(u1,v,x,'0.05','18',u2,'Vienna Lager',u3),Locations(u4,v,y,z,w).",
+    )
+    .evaluate(&database);
+    let anonymous = parse_query(
+        "Answer(x,y,z,w):-Beers(_,v,x,'0.05','18',_,'Vienna Lager',_),Locations(_,v,y,z,w).",
+    )
+    .evaluate(&database);
+    assert_eq!(anonymous.get_data().num_rows(), 1);
+    for i in 0..4 {
+        assert_eq!(anonymous.get_column_as_vec(i), named.get_column_as_vec(i));
+    }
+}
+
+#[test]
+fn named_terms_are_placed_by_column() {
+    let database = get_database();
+    let query = try_parse_query(
+        "Answer(x):-Breweries(brew_id: x, city: 'Brussels').",
+        &database,
+    )
+    .unwrap();
+    let breweries = &query.body[0];
+    assert_eq!(breweries.terms.len(), 11);
+    assert_eq!(breweries.terms[0], Term::Variable("x".to_string()));
+    assert_eq!(breweries.terms[4], Term::Constant("Brussels".to_string()));
+    assert!(breweries.terms[1].is_anonymous());
+
+    let program = parse_program(
+        "Located(brewery):-Locations(brew_id: brewery).\nAnswer(x):-Located(brewery: x).",
+        &database,
+    );
+    assert_eq!(
+        program.rules[1].body[0].terms,
+        vec![Term::Variable("x".to_string())]
+    );
+}
+
+#[test]
+fn unknown_columns_are_rejected() {
+    assert_eq!(
+        try_parse_query(
+            "Answer(x):-Breweries(brew_id: x, town: 'Brussels').",
+            &get_database()
+        )
+        .unwrap_err(),
+        "breweries has no column town"
+    );
+}
+
+#[test]
+fn quoted_constants_and_identifiers() {
+    let query = parse_query(
+        "Answer(brew_id):-\"Brewers\"(brew_id,'Sam''s',\"Sam's\",'Sam\\'s','K\\u{f6}lsch','a\\\\b').",
+    );
+    assert_eq!(query.body[0].relation_name, "Brewers");
+    assert_eq!(
+        query.head.terms,
+        vec![Term::Variable("brew_id".to_string())]
+    );
+    let constants = query.body[0].terms[1..]
+        .iter()
+        .map(|term| match term {
+            Term::Constant(constant) => constant.as_str(),
+            Term::Variable(_) => panic!("expected a constant"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(constants, vec!["Sam's", "Sam's", "Sam's", "Kölsch", "a\\b"]);
+    assert_eq!(
+        query.to_string(),
+        "answer(brew_id):-\"Brewers\"(brew_id,'Sam''s','Sam''s','Sam''s','Kölsch','a\\\\b')."
+    );
+    assert_eq!(parse_query(&query.to_string()), query);
+    assert_eq!(
+        parse_query("Answer(x):-BEERS(x).").body[0].relation_name,
+        "beers"
+    );
+}
+
+#[test]
+fn named_terms_resolve_against_the_queried_database() {
+    let mut database = get_database();
+    parse_program(
+        "Pairs(style, category):-Styles(_, category, style).",
+        &database,
+    )
+    .evaluate(&mut database)
+    .unwrap();
+    let query = try_parse_query("Answer(x):-Pairs(category: '1', style: x).", &database).unwrap();
+    assert_eq!(
+        query.body[0].terms[..2],
+        [
+            Term::Variable("x".to_string()),
+            Term::Constant("1".to_string())
+        ]
+    );
+    assert_eq!(
+        try_parse_query("Answer(x):-Pairs(style: x).", &get_database()).unwrap_err(),
+        "unknown relation pairs"
+    );
+    let program = parse_program("Ale(x):-Pairs(style: x, category: '1').", &database);
+    assert_eq!(program.rules[0].body[0].relation_name, "pairs");
+
+    // positional and named lists accept the same whitespace after their commas
+    assert_eq!(
+        parse_query("Answer(x, y):-Categories(x,  y)."),
+        parse_query("Answer(x,y):-Categories(x,y).")
+    );
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use query_engine_acq::data_structure::parser::parse_query;
+use query_engine_acq::data_structure::query::Query;
+use query_engine_acq::data_structure::reader::get_database;
+use query_engine_acq::data_structure::sql::{parse_sql, to_sql};
+
+use common::sorted_rows;
+
+fn rows(query: &Query) -> Vec<Vec<String>> {
+    sorted_rows(&query.evaluate(&get_database()))
+}
+
+#[test]
+fn select_from_where_matches_the_rule() {
+    let sql = parse_sql(
+        "SELECT b.beer, l.latitude, l.longitude, l.accuracy \
+         FROM beers b, locations AS l \
+         WHERE b.brew_id = l.brew_id AND b.abv = 0.05 AND b.ibu = '18' AND b.style = 'Vienna Lager'",
+    )
+    .unwrap();
+    assert!(sql.is_acyclic());
+    let rule = parse_query(
+        "Answer(x,y,z,w):-Beers(u1,v,x,'0.05','18',u2,'Vienna Lager',u3),Locations(u4,v,y,z,w).",
+    );
+    assert_eq!(rows(&sql), rows(&rule));
+}
+
+#[test]
+fn join_on_and_selected_constants() {
+    let sql = parse_sql(
+        "select s.style, cat_name from styles s join categories c on s.cat_id = c.cat_id \
+         where c.cat_id = '7'",
+    )
+    .unwrap();
+    let answer = rows(&sql);
+    assert!(!answer.is_empty());
+    assert!(answer.iter().all(|row| row[1] == "German Lager"));
+}
+
+#[test]
+fn non_conjunctive_sql_is_rejected() {
+    assert_eq!(
+        parse_sql("SELECT beer FROM beers WHERE abv = '0.05' OR abv = '0.06'").unwrap_err(),
+        "unsupported SQL near OR: disjunctions (OR) are not conjunctive, write one query per disjunct"
+    );
+    assert_eq!(
+        parse_sql("SELECT beer FROM beers WHERE abv > '0.05'").unwrap_err(),
+        "unsupported SQL near >: only equality comparisons can be expressed"
+    );
+    assert_eq!(
+        parse_sql("SELECT brew_id FROM beers, locations").unwrap_err(),
+        "ambiguous column brew_id"
+    );
+    assert_eq!(
+        parse_sql("SELECT COUNT(beer) FROM beers").unwrap_err(),
+        "unsupported SQL near (: aggregation is outside the conjunctive fragment"
+    );
+    assert_eq!(
+        parse_sql("SELECT beer FROM beers WHERE brew_id IN (SELECT brew_id FROM breweries)")
+            .unwrap_err(),
+        "unsupported SQL near IN: nested queries are not conjunctive queries"
+    );
+    assert_eq!(
+        parse_sql("SELECT beer FROM beers b LEFT JOIN breweries r ON b.brew_id = r.brew_id")
+            .unwrap_err(),
+        "unsupported SQL near LEFT: only inner joins can be expressed"
+    );
+    assert_eq!(
+        parse_sql("SELECT beer AS _x FROM beers").unwrap_err(),
+        "names cannot start with _: _x"
+    );
+}
+
+#[test]
+fn keywords_of_unsupported_constructs_name_columns() {
+    let sql = parse_sql(
+        "SELECT count.cat_name AS min, max.style AS left \
+         FROM categories AS count, styles AS max WHERE count.cat_id = max.cat_id",
+    )
+    .unwrap();
+    let rule = parse_query("Answer(x,y):-Categories(c,x),Styles(_,c,y).");
+    assert_eq!(rows(&sql), rows(&rule));
+}
+
+#[test]
+fn a_column_compared_with_two_constants_has_no_answers() {
+    let sql =
+        parse_sql("SELECT beer FROM beers s WHERE s.abv = '0.05' AND s.abv = '0.06'").unwrap();
+    assert!(rows(&sql).is_empty());
+    let one = parse_sql("SELECT beer FROM beers s WHERE s.abv = '0.05'").unwrap();
+    assert!(!rows(&one).is_empty());
+}
+
+#[test]
+fn query_round_trips_through_datalog_and_sql() {
+    let query = parse_query(
+        "Answer(x,y,z,w):-Beers(_,v,x,'0.05','18',_,'Vienna Lager',_),Locations(_,v,y,z,w).",
+    );
+    assert_eq!(
+        query.to_string(),
+        "answer(x,y,z,w):-beers(_,v,x,'0.05','18',_,'Vienna Lager',_),locations(_,v,y,z,w)."
+    );
+    assert_eq!(parse_query(&query.to_string()), query);
+
+    let sql = to_sql(&query).unwrap();
+    assert_eq!(
+        sql,
+        "SELECT DISTINCT t1.beer AS x, t2.latitude AS y, t2.longitude AS z, t2.accuracy AS w \
+         FROM beers t1, locations t2 \
+         WHERE t1.abv = '0.05' AND t1.ibu = '18' AND t1.style = 'Vienna Lager' \
+         AND t2.brew_id = t1.brew_id"
+    );
+    assert_eq!(rows(&parse_sql(&sql).unwrap()), rows(&query));
+}
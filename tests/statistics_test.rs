@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use query_engine_acq::data_structure::cost::{atom_cardinality, join_cardinality};
+use query_engine_acq::data_structure::database::Database;
+use query_engine_acq::data_structure::parser::parse_query;
+use query_engine_acq::data_structure::reader::get_database;
+
+#[test]
+fn statistics_describe_every_column() {
+    let database = get_database();
+    let styles = database.get_table("styles");
+    let statistics = database.statistics("styles");
+    assert_eq!(*statistics, styles.statistics());
+    assert_eq!(statistics.rows, styles.get_data().num_rows());
+    assert_eq!(statistics.columns.len(), 3);
+    for (index, column) in statistics.columns.iter().enumerate() {
+        let mut values = styles.get_column_as_vec(index).unwrap();
+        values.sort();
+        values.dedup();
+        assert_eq!(column.distinct, values.len());
+        assert_eq!(column.nulls, 0);
+        let common = column
+            .most_common
+            .iter()
+            .map(|(_, rows)| rows)
+            .sum::<usize>();
+        let histogram = column
+            .histogram
+            .buckets
+            .iter()
+            .map(|bucket| bucket.rows)
+            .sum::<usize>();
+        assert_eq!(common + histogram + column.nulls, statistics.rows);
+    }
+    // style ids are unique, category ids are shared by many styles
+    assert!(statistics.columns[0].most_common.is_empty());
+    let (category, rows) = &statistics.columns[1].most_common[0];
+    let exact = styles
+        .get_column_as_vec(1)
+        .unwrap()
+        .iter()
+        .filter(|value| *value == category)
+        .count();
+    assert_eq!(*rows, exact);
+    let selectivity = statistics.equality_selectivity(1, category);
+    assert_eq!(selectivity, exact as f64 / statistics.rows as f64);
+    assert_eq!(
+        statistics.equality_selectivity(0, "1"),
+        1.0 / statistics.rows as f64
+    );
+}
+
+#[test]
+fn cardinalities_are_estimated_from_the_statistics() {
+    let database = get_database();
+    let query = parse_query("Answer(x):-Styles(_,c,x),Categories(c,'British Ale').");
+    let styles = &query.body[0];
+    assert_eq!(
+        atom_cardinality(styles, &database),
+        database.statistics("styles").rows as f64
+    );
+    let british_ale = &query.body[1];
+    assert_eq!(atom_cardinality(british_ale, &database), 1.0);
+    // the styles of one category out of all of them, which the estimate is close to
+    let rows = database.statistics("styles").rows as f64;
+    let categories = database.statistics("styles").columns[1]
+        .distinct
+        .max(database.statistics("categories").columns[0].distinct);
+    let estimate = join_cardinality(styles, rows, british_ale, 1.0, &database);
+    assert_eq!(estimate, rows / categories as f64);
+    let evaluated = query.evaluate(&database).get_data().num_rows() as f64;
+    assert!(estimate > evaluated / 2.0 && estimate < evaluated * 2.0);
+    let plan = query.explain(&database).to_string();
+    assert!(plan.contains(&format!(
+        "estimated rows after selection:\n    \
+         categories(c,'British Ale'): 1.0\n    \
+         styles(_,c,x): {:.1}\n",
+        rows
+    )));
+}
+
+#[test]
+fn statistics_are_computed_once_until_the_table_changes() {
+    let mut database = Database::default();
+    database.add_table(get_database().get_table("styles").clone());
+    let statistics = database.statistics("styles");
+    assert!(Arc::ptr_eq(&statistics, &database.statistics("styles")));
+    // views share the statistics of the tables they rename
+    let query = parse_query("Answer(x):-Styles(_,c,x).");
+    assert!(Arc::ptr_eq(
+        &statistics,
+        &database.view(&query).statistics("styles")
+    ));
+
+    let table = database.get_table("styles").clone();
+    database.set_table("styles", table);
+    assert!(!Arc::ptr_eq(&statistics, &database.statistics("styles")));
+}
@@ -0,0 +1,53 @@
+use query_engine_acq::data_structure::parser::parse_query;
+use query_engine_acq::data_structure::reader::{get_database, scan};
+use query_engine_acq::data_structure::stream::BATCH_SIZE;
+
+#[test]
+fn files_are_scanned_in_bounded_batches() {
+    let batches = scan("beers").unwrap().collect::<Vec<_>>();
+    assert!(batches.len() > 1 || batches[0].num_rows() <= BATCH_SIZE);
+    assert!(batches.iter().all(|batch| batch.num_rows() <= BATCH_SIZE));
+    let rows = batches.iter().map(|batch| batch.num_rows()).sum::<usize>();
+    assert_eq!(
+        rows,
+        get_database().get_table("beers").get_data().num_rows()
+    );
+    assert!(scan("unknown").is_none());
+}
+
+#[test]
+fn streamed_answers_match_the_evaluated_ones() {
+    let database = get_database();
+    for query in [
+        "Answer(n,c,y):-Beers(_,v,n,_,_,_,_,_),Breweries(v,_,c,_,_,_,_,_,_,_,_),Locations(_,v,y,_,_).",
+        "Answer(x,y):-Categories(a,x),Styles(b,c,y).",
+        "Answer(x):-Styles(_,c,x),Categories(c,'British Ale').",
+    ] {
+        let query = parse_query(query);
+        let evaluated = query.evaluate(&database);
+        let stream = query.stream(&database);
+        assert_eq!(stream.schema(), evaluated.get_data().schema());
+        let mut rows = 0;
+        for batch in stream {
+            assert!(batch.num_rows() <= BATCH_SIZE);
+            rows += batch.num_rows();
+        }
+        assert_eq!(rows, evaluated.get_data().num_rows());
+    }
+}
+
+#[test]
+fn answers_are_written_as_csv() {
+    let query =
+        parse_query("Answer(x,k):-Styles(_,c,x),Categories(c,k),Categories(c,'British Ale').");
+    let mut output = vec![];
+    let rows = query
+        .stream(&get_database())
+        .write_csv(&mut output)
+        .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let mut lines = output.lines();
+    assert_eq!(lines.next(), Some("x,k"));
+    assert_eq!(lines.count(), rows);
+    assert!(output.contains("Classic English-Style Pale Ale,British Ale"));
+}
@@ -42,3 +42,32 @@ fn example_query_4() {
         ]
     );
 }
+
+#[test]
+fn every_join_tree_gives_the_same_answer() {
+    use std::collections::HashSet;
+
+    let query = query_engine_acq::data_structure::parser::parse_query(
+        "Answer(n,c,y):-Beers(_,v,n,_,_,_,_,_),Breweries(v,_,c,_,_,_,_,_,_,_,_),Locations(_,v,y,_,_).",
+    );
+    let database = query_engine_acq::data_structure::reader::get_database_with_query(&query);
+    let join_trees = query.join_trees();
+    assert!(join_trees
+        .iter()
+        .any(|join_tree| join_tree.get_children(join_tree.get_root()).len() == 2));
+    let mut answers = join_trees.iter().map(|join_tree| {
+        let answer = query.yannakakis_with_join_tree(database.clone(), join_tree);
+        (0..answer.get_data().num_rows())
+            .map(|row| {
+                (0..3)
+                    .map(|column| answer.get_column(column).unwrap().value(row).to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<HashSet<_>>()
+    });
+    let first = answers.next().unwrap();
+    assert!(first.len() > 1000);
+    for answer in answers {
+        assert_eq!(answer, first);
+    }
+}